@@ -0,0 +1,361 @@
+use crate::geom::Vec2;
+use assets_manager::AssetCache;
+use frenderer::sprites::SheetRegion;
+use std::collections::HashMap;
+use std::str::FromStr;
+
+/// What an entity is doing, which picks the animation it shows.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub enum AnimState {
+    #[default]
+    Idle,
+    WalkLeft,
+    WalkRight,
+    WalkUp,
+    WalkDown,
+    Death,
+}
+
+impl AnimState {
+    const ALL: [Self; 6] = [
+        Self::Idle,
+        Self::WalkLeft,
+        Self::WalkRight,
+        Self::WalkUp,
+        Self::WalkDown,
+        Self::Death,
+    ];
+    /// The name animation files use for the state.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Idle => "idle",
+            Self::WalkLeft => "walk-left",
+            Self::WalkRight => "walk-right",
+            Self::WalkUp => "walk-up",
+            Self::WalkDown => "walk-down",
+            Self::Death => "death",
+        }
+    }
+    /// Walking whichever way `delta` mostly goes, or idle if it's zero.
+    pub fn moving(delta: Vec2) -> Self {
+        let Vec2 { x: dx, y: dy } = delta;
+        if dx == 0.0 && dy == 0.0 {
+            Self::Idle
+        } else if dx.abs() >= dy.abs() {
+            if dx > 0.0 {
+                Self::WalkRight
+            } else {
+                Self::WalkLeft
+            }
+        } else if dy > 0.0 {
+            Self::WalkDown
+        } else {
+            Self::WalkUp
+        }
+    }
+}
+
+impl FromStr for AnimState {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, ()> {
+        Self::ALL
+            .into_iter()
+            .find(|state| state.name() == s)
+            .ok_or(())
+    }
+}
+
+/// Sheet regions shown one after another, each for `frame_time` seconds.
+#[derive(Clone, Debug)]
+pub struct Animation {
+    frames: Vec<SheetRegion>,
+    frame_time: f32,
+    // otherwise it stops on the last frame
+    looping: bool,
+}
+
+impl Animation {
+    /// The frame showing `time` seconds after the animation started.
+    pub fn frame(&self, time: f32) -> SheetRegion {
+        let frame = (time.max(0.0) / self.frame_time) as usize;
+        if self.looping {
+            self.frames[frame % self.frames.len()]
+        } else {
+            self.frames[frame.min(self.frames.len() - 1)]
+        }
+    }
+}
+
+/// One entity's animations.  States without one of their own show the
+/// idle animation.
+#[derive(Clone, Debug, Default)]
+pub struct AnimationSet {
+    animations: HashMap<AnimState, Animation>,
+}
+
+impl AnimationSet {
+    /// A set that always shows `region`.
+    pub fn still(region: SheetRegion) -> Self {
+        let idle = Animation {
+            frames: vec![region],
+            frame_time: 1.0,
+            looping: true,
+        };
+        Self {
+            animations: HashMap::from([(AnimState::Idle, idle)]),
+        }
+    }
+    /// What to draw `time` seconds into `state`.
+    pub fn region(&self, state: AnimState, time: f32) -> SheetRegion {
+        self.animations
+            .get(&state)
+            .or_else(|| self.animations.get(&AnimState::Idle))
+            .map_or(SheetRegion::ZERO, |animation| animation.frame(time))
+    }
+}
+
+/// A line of an animations file that can't be used, numbered from 1.
+#[derive(Debug, PartialEq, Eq)]
+pub enum AnimationParseError {
+    /// The file doesn't start with `animations 1`.
+    MissingHeader,
+    /// The state is missing or isn't one of [`AnimState`]'s names.
+    BadState { line: usize },
+    /// The seconds per frame are missing, not a number or not above zero.
+    BadFrameTime { line: usize },
+    /// Neither `loop` nor `once` follows the frame time.
+    BadLooping { line: usize },
+    /// There are no frames, or one isn't four whole numbers.
+    BadFrames { line: usize },
+    /// The entity already has an animation for the state.
+    DuplicateAnimation {
+        line: usize,
+        entity: String,
+        state: AnimState,
+    },
+}
+
+impl AnimationParseError {
+    pub fn line(&self) -> usize {
+        match self {
+            Self::MissingHeader => 1,
+            Self::BadState { line }
+            | Self::BadFrameTime { line }
+            | Self::BadLooping { line }
+            | Self::BadFrames { line }
+            | Self::DuplicateAnimation { line, .. } => *line,
+        }
+    }
+}
+
+impl std::fmt::Display for AnimationParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: ", self.line())?;
+        match self {
+            Self::MissingHeader => write!(f, "expected `animations 1` first"),
+            Self::BadState { .. } => write!(
+                f,
+                "the state should be idle, walk-left, walk-right, walk-up, walk-down or death"
+            ),
+            Self::BadFrameTime { .. } => {
+                write!(f, "frames need to last a number of seconds above zero")
+            }
+            Self::BadLooping { .. } => write!(f, "expected `loop` or `once` after the seconds"),
+            Self::BadFrames { .. } => write!(f, "frames look like `X Y W H`, at least one of them"),
+            Self::DuplicateAnimation { entity, state, .. } => {
+                write!(f, "{entity} {} is already animated above", state.name())
+            }
+        }
+    }
+}
+
+impl std::error::Error for AnimationParseError {}
+
+/// Every entity's animations, keyed by entity name.
+#[derive(Debug, Default)]
+pub struct Animations {
+    sets: HashMap<String, AnimationSet>,
+}
+
+impl Animations {
+    /// Reads `NAME.txt` from the asset cache.
+    pub fn load(cache: &AssetCache, name: &str) -> Result<Self, String> {
+        let handle = cache
+            .load::<String>(name)
+            .map_err(|_| format!("{name}.txt: couldn't load animations"))?;
+        let text = handle.read();
+        text.parse().map_err(|e| format!("{name}.txt:{e}"))
+    }
+    pub fn get(&self, entity: &str) -> Option<&AnimationSet> {
+        self.sets.get(entity)
+    }
+}
+
+/*
+Animations are listed one to a line, each with the entity and state it's
+for, how many seconds each frame lasts, whether it loops or stops on its
+last frame, and the sheet region of each frame.  A negative W or H flips
+the frame.
+
+animations 1
+ENTITY STATE SECS loop|once X Y W H [X Y W H...]
+ENTITY STATE SECS loop|once X Y W H [X Y W H...]
+...
+
+STATE is one of idle, walk-left, walk-right, walk-up, walk-down and death.
+*/
+impl FromStr for Animations {
+    type Err = AnimationParseError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut lines = s.lines().enumerate().map(|(idx, line)| (idx + 1, line));
+        match lines.next() {
+            Some((_, "animations 1")) => {}
+            _ => return Err(AnimationParseError::MissingHeader),
+        }
+        let mut animations = Animations::default();
+        for (line, text) in lines {
+            let mut fields = text.split_whitespace();
+            let Some(entity) = fields.next() else {
+                continue;
+            };
+            let state: AnimState = fields
+                .next()
+                .and_then(|state| state.parse().ok())
+                .ok_or(AnimationParseError::BadState { line })?;
+            let frame_time = fields
+                .next()
+                .and_then(|secs| secs.parse::<f32>().ok())
+                .filter(|secs| *secs > 0.0 && secs.is_finite())
+                .ok_or(AnimationParseError::BadFrameTime { line })?;
+            let looping = match fields.next() {
+                Some("loop") => true,
+                Some("once") => false,
+                _ => return Err(AnimationParseError::BadLooping { line }),
+            };
+            let coords: Vec<&str> = fields.collect();
+            let frames = coords
+                .chunks(4)
+                .map(|frame| match frame {
+                    [x, y, w, h] => Some(SheetRegion::new(
+                        0,
+                        x.parse().ok()?,
+                        y.parse().ok()?,
+                        0,
+                        w.parse().ok()?,
+                        h.parse().ok()?,
+                    )),
+                    _ => None,
+                })
+                .collect::<Option<Vec<_>>>()
+                .filter(|frames| !frames.is_empty())
+                .ok_or(AnimationParseError::BadFrames { line })?;
+            let set = animations.sets.entry(entity.to_string()).or_default();
+            let animation = Animation {
+                frames,
+                frame_time,
+                looping,
+            };
+            if set.animations.insert(state, animation).is_some() {
+                return Err(AnimationParseError::DuplicateAnimation {
+                    line,
+                    entity: entity.to_string(),
+                    state,
+                });
+            }
+        }
+        Ok(animations)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    const HERO: &str = "animations 1
+hero idle 0.5 loop 0 0 16 16
+
+hero walk-right 0.1 loop 16 0 16 16 32 0 16 16
+hero death 0.2 once 48 0 16 16 64 0 16 -16
+";
+    fn xy(region: SheetRegion) -> (u16, u16) {
+        (region.x, region.y)
+    }
+    #[test]
+    fn test_frames() {
+        let animations: Animations = HERO.parse().unwrap();
+        let hero = animations.get("hero").unwrap();
+        assert_eq!(xy(hero.region(AnimState::Idle, 3.0)), (0, 0));
+        assert_eq!(xy(hero.region(AnimState::WalkRight, 0.05)), (16, 0));
+        assert_eq!(xy(hero.region(AnimState::WalkRight, 0.15)), (32, 0));
+        assert_eq!(xy(hero.region(AnimState::WalkRight, 0.25)), (16, 0));
+        // death stays down
+        assert_eq!(xy(hero.region(AnimState::Death, 0.0)), (48, 0));
+        assert_eq!(hero.region(AnimState::Death, 10.0).h, -16);
+        // no walk-left of its own
+        assert_eq!(xy(hero.region(AnimState::WalkLeft, 0.15)), (0, 0));
+        assert!(animations.get("villain").is_none());
+        let still = AnimationSet::still(SheetRegion::new(0, 5, 6, 0, 16, 16));
+        assert_eq!(xy(still.region(AnimState::WalkUp, 7.0)), (5, 6));
+    }
+    #[test]
+    fn test_moving() {
+        assert_eq!(AnimState::moving(Vec2::new(0.0, 0.0)), AnimState::Idle);
+        assert_eq!(
+            AnimState::moving(Vec2::new(0.1, -0.05)),
+            AnimState::WalkRight
+        );
+        assert_eq!(AnimState::moving(Vec2::new(-0.1, 0.0)), AnimState::WalkLeft);
+        assert_eq!(AnimState::moving(Vec2::new(0.05, -0.1)), AnimState::WalkUp);
+        assert_eq!(AnimState::moving(Vec2::new(0.0, 0.1)), AnimState::WalkDown);
+    }
+    #[test]
+    fn test_content() {
+        let text = std::fs::read_to_string("content/animations.txt").unwrap();
+        let animations: Animations = text.parse().unwrap_or_else(|e| panic!("{e}"));
+        for entity in ["player", "enemy", "gold"] {
+            assert!(animations.get(entity).is_some(), "no {entity} animations");
+        }
+        // a dead or left-walking player doesn't look like a standing one
+        let player = animations.get("player").unwrap();
+        let look = |state| {
+            let region = player.region(state, 0.0);
+            (region.x, region.y, region.w, region.h)
+        };
+        assert_ne!(look(AnimState::Death), look(AnimState::Idle));
+        assert_ne!(look(AnimState::WalkLeft), look(AnimState::WalkRight));
+    }
+    #[test]
+    fn test_parse_errors() {
+        let err = |s: &str| s.parse::<Animations>().unwrap_err();
+        assert_eq!(err(""), AnimationParseError::MissingHeader);
+        assert_eq!(err("animations 2\n").line(), 1);
+        let line = |s: &str| err(&format!("animations 1\n{s}\n"));
+        assert_eq!(
+            line("hero fly 0.1 loop 0 0 16 16"),
+            AnimationParseError::BadState { line: 2 }
+        );
+        assert_eq!(
+            line("hero idle 0 loop 0 0 16 16"),
+            AnimationParseError::BadFrameTime { line: 2 }
+        );
+        assert_eq!(
+            line("hero idle 0.1 twice 0 0 16 16"),
+            AnimationParseError::BadLooping { line: 2 }
+        );
+        for frames in ["", "0 0 16", "0 0 16 16 1"] {
+            assert_eq!(
+                line(&format!("hero idle 0.1 loop {frames}")),
+                AnimationParseError::BadFrames { line: 2 }
+            );
+        }
+        let twice = err(&format!("{HERO}hero idle 1 loop 0 0 16 16\n"));
+        assert_eq!(
+            twice,
+            AnimationParseError::DuplicateAnimation {
+                line: 6,
+                entity: "hero".to_string(),
+                state: AnimState::Idle
+            }
+        );
+        assert_eq!(twice.to_string(), "6: hero idle is already animated above");
+    }
+}
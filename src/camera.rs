@@ -0,0 +1,106 @@
+use frenderer::sprites::Camera2D;
+
+// How far the target can stray from the middle of the screen before the
+// camera follows, as a fraction of the screen size.
+const DEAD_ZONE: f32 = 0.15;
+// How quickly the camera closes the gap to where it wants to be; after
+// 1/SMOOTHING seconds about two thirds of the gap is gone.
+const SMOOTHING: f32 = 8.0;
+
+/// Keeps a target, usually the player, on screen without following every
+/// little movement, and without showing anything outside the level.
+/// Everything is in the same pixel coordinates as sprite transforms.
+pub struct CameraController {
+    size: [f32; 2],
+    // the middle of the screen
+    center: [f32; 2],
+}
+
+impl CameraController {
+    pub fn new(size: [f32; 2]) -> Self {
+        Self {
+            size,
+            center: [size[0] / 2.0, size[1] / 2.0],
+        }
+    }
+
+    /// Jumps straight to `target`, e.g. on entering a level.
+    pub fn snap_to(&mut self, target: [f32; 2], bounds: [f32; 2]) {
+        self.center = target;
+        self.clamp(bounds);
+    }
+
+    /// Moves towards `target` over `dt` seconds, staying inside a level
+    /// `bounds` pixels in size.
+    pub fn update(&mut self, target: [f32; 2], bounds: [f32; 2], dt: f32) {
+        let blend = 1.0 - (-SMOOTHING * dt).exp();
+        let follow = |center: f32, target: f32, size: f32| {
+            let slack = size * DEAD_ZONE;
+            let wanted = center.clamp(target - slack, target + slack);
+            center + (wanted - center) * blend
+        };
+        self.center = [
+            follow(self.center[0], target[0], self.size[0]),
+            follow(self.center[1], target[1], self.size[1]),
+        ];
+        self.clamp(bounds);
+    }
+
+    fn clamp(&mut self, bounds: [f32; 2]) {
+        let clamp = |center: f32, size: f32, bound: f32| {
+            if bound <= size {
+                // a level smaller than the screen sits in the middle of it
+                bound / 2.0
+            } else {
+                center.clamp(size / 2.0, bound - size / 2.0)
+            }
+        };
+        self.center = [
+            clamp(self.center[0], self.size[0], bounds[0]),
+            clamp(self.center[1], self.size[1], bounds[1]),
+        ];
+    }
+
+    pub fn camera(&self) -> Camera2D {
+        Camera2D {
+            screen_pos: [
+                self.center[0] - self.size[0] / 2.0,
+                self.center[1] - self.size[1] / 2.0,
+            ],
+            screen_size: self.size,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    const BOUNDS: [f32; 2] = [1000.0, 240.0];
+    #[test]
+    fn test_dead_zone() {
+        let mut cam = CameraController::new([320.0, 240.0]);
+        cam.snap_to([160.0, 120.0], BOUNDS);
+        cam.update([190.0, 120.0], BOUNDS, 1.0);
+        assert_eq!(cam.camera().screen_pos, [0.0, 0.0]);
+        // far outside the dead zone it closes in without jumping
+        cam.update([600.0, 120.0], BOUNDS, 0.05);
+        let x = cam.camera().screen_pos[0];
+        assert!(x > 0.0 && x < 600.0 - 160.0 - 48.0, "{x}");
+        for _ in 0..100 {
+            cam.update([600.0, 120.0], BOUNDS, 0.05);
+        }
+        let x = cam.camera().screen_pos[0];
+        assert!((x - (600.0 - 160.0 - 48.0)).abs() < 0.01, "{x}");
+    }
+    #[test]
+    fn test_clamped_to_level() {
+        let mut cam = CameraController::new([320.0, 240.0]);
+        cam.snap_to([990.0, 10.0], BOUNDS);
+        assert_eq!(cam.camera().screen_pos, [680.0, 0.0]);
+        cam.snap_to([-50.0, 500.0], BOUNDS);
+        assert_eq!(cam.camera().screen_pos, [0.0, 0.0]);
+        // smaller than the screen: centered
+        cam.snap_to([10.0, 10.0], [160.0, 120.0]);
+        assert_eq!(cam.camera().screen_pos, [-80.0, -60.0]);
+    }
+}
@@ -0,0 +1,85 @@
+use frenderer::clock::Instant;
+
+// Frame times within this many seconds of a common refresh interval are
+// treated as exactly that interval, so vsync jitter doesn't make frames
+// alternate between one and two simulation steps.
+const SNAP_TOLERANCE: f32 = 0.0002;
+const REFRESH_RATES: [f32; 5] = [30.0, 60.0, 75.0, 120.0, 144.0];
+
+/// Turns wall-clock time between frames into a number of fixed-length
+/// simulation steps.  Time is banked between frames and spent a step at a
+/// time; what's left over says how far the next step has got, for
+/// blending rendering between the last two steps.
+pub struct Clock {
+    dt: f32,
+    // the most steps one frame may run; more is dropped rather than owed
+    max_steps: usize,
+    acc: f32,
+    last: Instant,
+}
+
+impl Clock {
+    pub fn new(dt: f32, max_steps: usize) -> Self {
+        Self {
+            dt,
+            max_steps,
+            acc: 0.0,
+            last: Instant::now(),
+        }
+    }
+
+    /// Call once a frame; returns how many steps to simulate.
+    pub fn tick(&mut self) -> usize {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last).as_secs_f32();
+        self.last = now;
+        self.advance(elapsed)
+    }
+
+    fn advance(&mut self, elapsed: f32) -> usize {
+        let elapsed = REFRESH_RATES
+            .iter()
+            .map(|rate| 1.0 / rate)
+            .find(|interval| (elapsed - interval).abs() < SNAP_TOLERANCE)
+            .unwrap_or(elapsed);
+        self.acc += elapsed;
+        let steps = (self.acc / self.dt) as usize;
+        self.acc -= steps as f32 * self.dt;
+        // After a stall (a dragged window, a breakpoint) catching up would
+        // take longer than the frame it's catching up on, and the next
+        // frame would be even further behind.
+        steps.min(self.max_steps)
+    }
+
+    /// How far between the last step and the next one the current frame
+    /// is, from 0 to 1.
+    pub fn alpha(&self) -> f32 {
+        (self.acc / self.dt).clamp(0.0, 1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_steps() {
+        let mut clock = Clock::new(0.02, 5);
+        assert_eq!(clock.advance(0.01), 0);
+        assert!((clock.alpha() - 0.5).abs() < 1e-4);
+        assert_eq!(clock.advance(0.035), 2);
+        assert!((clock.alpha() - 0.25).abs() < 1e-3);
+        // a long stall runs a few steps and forgets the rest
+        assert_eq!(clock.advance(3.0), 5);
+        assert_eq!(clock.advance(0.01), 0);
+        assert!(clock.alpha() < 1.0);
+    }
+    #[test]
+    fn test_snapping() {
+        let mut clock = Clock::new(1.0 / 60.0, 5);
+        // slightly short 60Hz frames still take a step each
+        for _ in 0..100 {
+            assert_eq!(clock.advance(1.0 / 60.0 - 0.0001), 1);
+        }
+        assert!(clock.alpha() < 0.01);
+    }
+}
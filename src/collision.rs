@@ -0,0 +1,356 @@
+use crate::geom::{Aabb, Vec2};
+use crate::level::Level;
+use crate::TileFlags;
+
+// Boxes resting exactly against a tile can end up a rounding error inside
+// it; overlaps this small don't count.
+const EPSILON: f32 = 1e-4;
+
+/// Which face of a tile a moving box ran into.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Face {
+    Left,
+    Right,
+    Top,
+    Bottom,
+}
+
+/// A solid tile that stopped a box, which can be just outside the level.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct TileHit {
+    pub tile: (i32, i32),
+    pub face: Face,
+}
+
+/// Moves `aabb` by `delta` through `level`, stopping against tiles with
+/// any of the flags in `blocked_by` and sliding along them.  One-way tiles
+/// also stop it unless it comes down into them through their top.  The
+/// tiles along the box's path are visited in the order it reaches them,
+/// diagonals included, so it stops at the first one it would touch.
+/// Returns where the middle of the box ends up and the tile faces it hit
+/// on the way.
+pub fn move_and_collide(
+    level: &Level,
+    aabb: Aabb,
+    delta: Vec2,
+    blocked_by: TileFlags,
+) -> (Vec2, Vec<TileHit>) {
+    let half = aabb.half;
+    let mut hits = vec![];
+    let mut center = aabb.center;
+    let mut target = aabb.center + delta;
+    // every hit stops the box along one axis, so this ends within two
+    // hits
+    loop {
+        let delta = target - center;
+        if delta == Vec2::ZERO {
+            return (target, hits);
+        }
+        let first = hits.len();
+        let Some(time) = first_hit(level, Aabb { center, half }, delta, blocked_by, &mut hits)
+        else {
+            return (target, hits);
+        };
+        center += delta * time;
+        for hit in &hits[first..] {
+            let (col, row) = (hit.tile.0 as f32, hit.tile.1 as f32);
+            match hit.face {
+                Face::Left => center.x = col - half.x,
+                Face::Right => center.x = col + 1.0 + half.x,
+                Face::Top => center.y = row - half.y,
+                Face::Bottom => center.y = row + 1.0 + half.y,
+            }
+            match hit.face {
+                Face::Left | Face::Right => target.x = center.x,
+                Face::Top | Face::Bottom => target.y = center.y,
+            }
+        }
+    }
+}
+
+// One axis of a box moving through the grid: the tile line its leading
+// edge crosses next, and when.
+struct Axis {
+    lead: f32,
+    d: f32,
+    line: f32,
+}
+
+impl Axis {
+    fn new(min: f32, max: f32, d: f32) -> Self {
+        // a box resting against a line, or a rounding error past it,
+        // still crosses it
+        if d > 0.0 {
+            Axis {
+                lead: max,
+                d,
+                line: (max - EPSILON).ceil(),
+            }
+        } else {
+            Axis {
+                lead: min,
+                d,
+                line: (min + EPSILON).floor(),
+            }
+        }
+    }
+    // from 0 to 1, or more if it's past the end of the move
+    fn time(&self) -> f32 {
+        if self.d == 0.0 {
+            f32::INFINITY
+        } else {
+            ((self.line - self.lead) / self.d).max(0.0)
+        }
+    }
+    // the row or column on the far side of `line`
+    fn lane(&self) -> i32 {
+        if self.d > 0.0 {
+            self.line as i32
+        } else {
+            self.line as i32 - 1
+        }
+    }
+    fn advance(&mut self) {
+        self.line += self.d.signum();
+    }
+}
+
+// Walks the tiles a box moving by `delta` reaches, in order, and adds to
+// `hits` the ones that stop it first.  Returns when, from 0 to 1, it
+// touches them.  Tiles it only meets corner to corner are left out when it
+// also meets a face.
+fn first_hit(
+    level: &Level,
+    body: Aabb,
+    delta: Vec2,
+    blocked_by: TileFlags,
+    hits: &mut Vec<TileHit>,
+) -> Option<f32> {
+    let (min, max) = (body.min(), body.max());
+    let mut x = Axis::new(min.x, max.x, delta.x);
+    let mut y = Axis::new(min.y, max.y, delta.y);
+    loop {
+        let time = x.time().min(y.time());
+        if time > 1.0 {
+            return None;
+        }
+        let found = hits.len();
+        // rows first when both lines are crossed at once, so a box meeting
+        // a corner head on lands on the top or bottom face
+        if y.time() == time {
+            let face = if delta.y > 0.0 {
+                Face::Top
+            } else {
+                Face::Bottom
+            };
+            let across = (min.x + delta.x * time, max.x + delta.x * time, delta.x);
+            let row = y.lane();
+            cross(
+                level,
+                blocked_by,
+                face,
+                across,
+                |col| (col, row),
+                hits,
+                found,
+            );
+            y.advance();
+        }
+        if x.time() == time {
+            let face = if delta.x > 0.0 {
+                Face::Left
+            } else {
+                Face::Right
+            };
+            let across = (min.y + delta.y * time, max.y + delta.y * time, delta.y);
+            let col = x.lane();
+            cross(
+                level,
+                blocked_by,
+                face,
+                across,
+                |row| (col, row),
+                hits,
+                found,
+            );
+            x.advance();
+        }
+        if hits.len() > found {
+            return Some(time);
+        }
+    }
+}
+
+// Adds to `hits` the tiles that stop a box entering a row or column through
+// `face` while it spans `lo` to `hi` across it, moving by `d` that way.
+// `tile` turns a place across the row or column into a tile.  If none of
+// the tiles it overlaps stop it, the one it's about to slide into
+// corner-first can, unless something was already hit since `found`.
+fn cross(
+    level: &Level,
+    blocked_by: TileFlags,
+    face: Face,
+    (lo, hi, d): (f32, f32, f32),
+    tile: impl Fn(i32) -> (i32, i32),
+    hits: &mut Vec<TileHit>,
+    found: usize,
+) {
+    let (first, end) = ((lo + EPSILON).floor() as i32, (hi - EPSILON).ceil() as i32);
+    let check = |at: i32, hits: &mut Vec<TileHit>| {
+        let (col, row) = tile(at);
+        let flags = level.flags_at(col, row);
+        if flags.intersects(blocked_by) || (flags.contains(TileFlags::ONE_WAY) && face != Face::Top)
+        {
+            hits.push(TileHit {
+                tile: (col, row),
+                face,
+            });
+        }
+    };
+    for at in first..end {
+        check(at, hits);
+    }
+    if hits.len() > found {
+        return;
+    }
+    if d > 0.0 && hi + EPSILON > end as f32 {
+        check(end, hits);
+    } else if d < 0.0 && lo - EPSILON < first as f32 {
+        check(first - 1, hits);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+    const SOLID: TileFlags = TileFlags::SOLID;
+
+    // a thin wall down the middle with a gap at the bottom
+    const HALL: &str = "hall 7 4
+===
+# s 0 0 16 16
+. o 0 0 16 16
+===
+# # # # # # #
+# . . # . . #
+# . . # . . #
+# . . . . . #
+===
+player 1 1
+";
+    fn level() -> Level {
+        Level::from_str(HALL).unwrap()
+    }
+    #[test]
+    fn test_no_tunneling() {
+        let level = level();
+        let player = Aabb::square(Vec2::new(1.5, 1.5), 0.5);
+        // far enough to jump the wall entirely in one step
+        let (center, hits) = move_and_collide(&level, player, Vec2::new(4.0, 0.0), SOLID);
+        assert_eq!(center, Vec2::new(2.75, 1.5));
+        assert_eq!(
+            hits,
+            vec![TileHit {
+                tile: (3, 1),
+                face: Face::Left
+            }]
+        );
+        let (center, hits) = move_and_collide(&level, player, Vec2::new(-4.0, 0.0), SOLID);
+        assert_eq!(center, Vec2::new(1.25, 1.5));
+        assert_eq!(hits[0].face, Face::Right);
+        // through the gap at the bottom nothing gets in the way
+        let low = Aabb::square(Vec2::new(1.5, 3.5), 0.5);
+        let (center, hits) = move_and_collide(&level, low, Vec2::new(4.0, 0.0), SOLID);
+        assert_eq!(center, Vec2::new(5.5, 3.5));
+        assert!(hits.is_empty());
+    }
+    #[test]
+    fn test_no_diagonal_tunneling() {
+        let level = Level::from_str(
+            "post 5 5\n===\n# s 0 0 16 16\n. o 0 0 16 16\n===\n. . . . .\n. . . . .\n. . # . .\n. . . . .\n. . . . .\n",
+        )
+        .unwrap();
+        // heading straight for the post's corner, then sliding off it
+        let player = Aabb::square(Vec2::new(1.5, 1.5), 0.5);
+        let (center, hits) = move_and_collide(&level, player, Vec2::new(2.0, 2.0), SOLID);
+        assert_eq!(center, Vec2::new(3.5, 1.75));
+        assert_eq!(
+            hits,
+            vec![TileHit {
+                tile: (2, 2),
+                face: Face::Top
+            }]
+        );
+        let player = Aabb::square(Vec2::new(3.5, 3.5), 0.5);
+        let (center, hits) = move_and_collide(&level, player, Vec2::new(-2.0, -2.0), SOLID);
+        assert_eq!(center, Vec2::new(1.5, 3.25));
+        assert_eq!(hits[0].face, Face::Bottom);
+        // a steeper path lands on the post's top face
+        let player = Aabb::square(Vec2::new(2.0, 0.5), 0.5);
+        let (center, hits) = move_and_collide(&level, player, Vec2::new(1.0, 4.0), SOLID);
+        assert_eq!(center, Vec2::new(3.0, 1.75));
+        assert_eq!(hits[0].tile, (2, 2));
+        assert_eq!(hits[0].face, Face::Top);
+        // only grazing the post's corner on the way past doesn't stop it
+        let player = Aabb::square(Vec2::new(2.0, 4.5), 0.5);
+        let (center, hits) = move_and_collide(&level, player, Vec2::new(2.5, -2.5), SOLID);
+        assert_eq!(center, Vec2::new(4.5, 2.0));
+        assert!(hits.is_empty());
+    }
+    #[test]
+    fn test_slides_along_walls() {
+        let level = level();
+        let player = Aabb::square(Vec2::new(1.5, 1.5), 0.5);
+        let (center, hits) = move_and_collide(&level, player, Vec2::new(3.0, -3.0), SOLID);
+        assert_eq!(center, Vec2::new(2.75, 1.25));
+        // it reaches the top wall first, then slides right into the middle
+        let faces: Vec<Face> = hits.iter().map(|h| h.face).collect();
+        assert_eq!(faces, vec![Face::Bottom, Face::Left]);
+        // resting against the wall, it can still move away or along it
+        let resting = Aabb::square(center, 0.5);
+        let (center, hits) = move_and_collide(&level, resting, Vec2::new(0.5, 1.0), SOLID);
+        assert_eq!(center, Vec2::new(2.75, 2.25));
+        assert_eq!(hits[0].face, Face::Left);
+        // a box over two rows is stopped by either
+        let tall = Aabb::new(Vec2::new(4.5, 2.5), Vec2::new(0.5, 1.5));
+        let (center, hits) = move_and_collide(&level, tall, Vec2::new(-2.0, 0.0), SOLID);
+        assert_eq!(center, Vec2::new(4.25, 2.5));
+        assert_eq!(hits.len(), 2);
+    }
+    #[test]
+    fn test_one_way_and_water() {
+        let level = Level::from_str(
+            "pond 3 4\n===\n. o 0 0 16 16\nv v 0 0 16 16\n~ w 0 0 16 16\n===\n. . .\nv v v\n. . .\n~ ~ ~\n",
+        )
+        .unwrap();
+        let above = Aabb::square(Vec2::new(1.5, 0.5), 0.5);
+        // down through the one-way row, but not back up it
+        let (center, hits) = move_and_collide(&level, above, Vec2::new(0.0, 2.0), SOLID);
+        assert_eq!(center, Vec2::new(1.5, 2.5));
+        assert!(hits.is_empty());
+        let below = Aabb::square(center, 0.5);
+        let (center, hits) = move_and_collide(&level, below, Vec2::new(0.0, -2.0), SOLID);
+        assert_eq!(center, Vec2::new(1.5, 2.25));
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].face, Face::Bottom);
+        // water only stops movers blocked by it
+        let (center, _) = move_and_collide(&level, below, Vec2::new(0.0, 1.0), SOLID);
+        assert_eq!(center, Vec2::new(1.5, 3.5));
+        let swimmers = SOLID | TileFlags::WATER;
+        let (center, hits) = move_and_collide(&level, below, Vec2::new(0.0, 1.0), swimmers);
+        assert_eq!(center, Vec2::new(1.5, 2.75));
+        assert_eq!(hits[0].face, Face::Top);
+    }
+    #[test]
+    fn test_level_edge_is_solid() {
+        let level = Level::from_str("open 2 1\n===\n. o 0 0 16 16\n===\n. .\n").unwrap();
+        let player = Aabb::square(Vec2::new(0.5, 0.5), 0.5);
+        let (center, hits) = move_and_collide(&level, player, Vec2::new(-1.0, -1.0), SOLID);
+        assert_eq!(center, Vec2::new(0.25, 0.25));
+        assert_eq!(hits.len(), 2);
+        // but not the tile it only meets corner to corner
+        assert_eq!(hits[0].tile, (0, -1));
+        assert_eq!(hits[1].tile, (-1, 0));
+    }
+}
@@ -0,0 +1,824 @@
+use crate::animation::AnimState;
+use crate::collision::move_and_collide;
+use crate::geom::*;
+use crate::grid::{Coord, Grid};
+use crate::initials::InitialsEntry;
+use crate::leaderboard::{Entry, Leaderboard};
+use crate::level::Level;
+use crate::replay::{Controls, Outcome};
+use crate::EntityType;
+use crate::TileFlags;
+use frenderer::input::{Input, Key};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use std::collections::HashMap;
+
+// in tiles
+pub const PLAYER_SIZE: f32 = 0.5;
+pub const ENEMY_SIZE: f32 = 0.75;
+// in tiles per second
+const ENEMY_SPEED: f32 = 2.0;
+// in seconds
+pub const ROUND_LENGTH: f32 = 60.0;
+pub const DEATH_DELAY: f32 = 3.0;
+// how much slow tiles scale the speed of whatever is on them
+const SLOW_FACTOR: f32 = 0.5;
+// how many pieces of gold a level keeps out at once
+const GOLD_COUNT: i32 = 50;
+// The tiles each kind of mover can't enter.  Enemies can't go down
+// one-way tiles either, since their paths don't know which way they go.
+const PLAYER_BLOCKED_BY: TileFlags = TileFlags::SOLID;
+const ENEMY_BLOCKED_BY: TileFlags = TileFlags::SOLID
+    .union(TileFlags::WATER)
+    .union(TileFlags::ONE_WAY);
+// Gold goes anywhere the player can safely pick it up.  Enemies only start
+// on plain floor.
+const GOLD_AVOIDS: TileFlags = TileFlags::SOLID
+    .union(TileFlags::DAMAGING)
+    .union(TileFlags::GOAL);
+
+#[derive(Clone)]
+pub struct Entity {
+    pub pos: Vec2,
+    // where the entity was before the latest step, for drawing between steps
+    pub prev_pos: Vec2,
+    pub dir: Vec2,
+    #[allow(dead_code)]
+    pattern: MovementPattern,
+    // tile being walked to, picked once the last one was reached
+    next: Option<Coord>,
+    pub anim: AnimState,
+    // value of `time` when `anim` last changed
+    pub anim_start: f32,
+}
+
+pub struct Door {
+    pub pos: Vec2,
+    to_level: String,
+    to_pos: Vec2,
+}
+
+#[derive(Clone, Copy)]
+pub enum MovementPattern {
+    Horizontal,
+    Vertical,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Ending {
+    Won,
+    Lost,
+}
+
+/// Which screen the game is on.  A run goes Title → Playing, maybe via
+/// Paused and back, then Dead or Won → Leaderboard → Title again.
+#[derive(Clone, Debug)]
+pub enum GameState {
+    Title,
+    Playing,
+    Paused,
+    // the player died and the run ends after DEATH_DELAY
+    Dead,
+    // the clock ran out and the player is typing their initials
+    Won(InitialsEntry),
+    Leaderboard,
+}
+
+impl GameState {
+    /// Whether `Game::update` simulates a step in this state.
+    pub fn is_running(&self) -> bool {
+        matches!(self, GameState::Playing | GameState::Dead)
+    }
+}
+
+/// What `Game::update` needs whoever is running the game to do.
+#[derive(Clone, PartialEq, Debug)]
+pub enum GameEvent {
+    /// A fresh run began.
+    RunStarted,
+    /// The run ended and its outcome won't change.
+    RunEnded(Ending),
+    /// A winning run was added to the leaderboard and should be saved.
+    Scored(Entry),
+    /// The player asked to leave from the title screen.
+    Quit,
+}
+
+/// Everything a run of the game needs apart from drawing it.
+pub struct Game {
+    pub state: GameState,
+    pub level: Level,
+    // the level every run starts in
+    start_level: String,
+    // every other level reachable through doors, keyed by name
+    world: HashMap<String, Level>,
+    pub doors: Vec<Door>,
+    // set while the player stands on the door they arrived through
+    on_door: bool,
+    // the player tile enemies are chasing and every tile's distance to it
+    chase_map: Option<(Coord, Grid<Option<u32>>)>,
+    pub player: Entity,
+    pub enemies: Vec<Entity>,
+    frame_counter: u32,
+    pub is_player_alive: bool,
+    // seconds simulated so far
+    pub time: f32,
+    // value of `time` when the player died
+    pub death_time: Option<f32>,
+    // set once the player reaches a goal tile
+    reached_goal: bool,
+    pub golds: Vec<Vec2>,
+    pub score: u32,
+    pub seed: u64,
+    // all randomness comes from here so a seed reproduces a run
+    rng: StdRng,
+    pub leaderboard: Leaderboard,
+}
+
+// The box `size` tiles across in the middle of an entity at `pos`.
+fn hitbox(pos: Vec2, size: f32) -> Aabb {
+    Aabb::square(cell_center(pos), size)
+}
+
+// The box covering tile `tile`.
+fn tile_box((x, y): Coord) -> Aabb {
+    Aabb::from(Rect {
+        x: x as u16,
+        y: y as u16,
+        w: 1,
+        h: 1,
+    })
+}
+
+// The tile under the middle of an entity at `pos`.
+fn tile_under(pos: Vec2) -> Coord {
+    world_to_tile(cell_center(pos)).unwrap_or((0, 0))
+}
+
+// The flags of the tile under the middle of an entity at `pos`.
+fn flags_under(level: &Level, pos: Vec2) -> TileFlags {
+    level
+        .get_tile(cell_center(pos))
+        .map_or(TileFlags::empty(), |tile| tile.flags)
+}
+
+// How fast something normally going `speed` goes on the tile under `pos`.
+fn speed_on(level: &Level, pos: Vec2, speed: f32) -> f32 {
+    if flags_under(level, pos).contains(TileFlags::SLOW) {
+        speed * SLOW_FACTOR
+    } else {
+        speed
+    }
+}
+
+impl Entity {
+    pub fn new_enemy(pos: Vec2, pattern: MovementPattern) -> Self {
+        let initial_dir = match pattern {
+            MovementPattern::Horizontal => Vec2::new(1.0, 0.0),
+            MovementPattern::Vertical => Vec2::new(0.0, 1.0),
+        };
+        Entity {
+            pos,
+            prev_pos: pos,
+            dir: initial_dir,
+            pattern,
+            next: None,
+            anim: AnimState::Idle,
+            anim_start: 0.0,
+        }
+    }
+
+    /// Switches to animation `anim` at sim time `time`, unless it's already
+    /// showing, so a new animation always starts from its first frame.
+    fn animate(&mut self, anim: AnimState, time: f32) {
+        if self.anim != anim {
+            self.anim = anim;
+            self.anim_start = time;
+        }
+    }
+
+    /// Where to draw the entity `alpha` of the way from its previous step
+    /// to its current one.
+    pub fn pos_at(&self, alpha: f32) -> Vec2 {
+        self.prev_pos.lerp(self.pos, alpha)
+    }
+}
+
+impl Game {
+    /// Sets up a run in `level` with `seed` driving everything random,
+    /// waiting on the title screen.
+    pub fn new(level: Level, seed: u64) -> Self {
+        let mut game = Game {
+            state: GameState::Title,
+            start_level: level.name().to_string(),
+            level,
+            world: HashMap::new(),
+            doors: Vec::new(),
+            on_door: false,
+            chase_map: None,
+            player: Entity {
+                pos: Vec2::ZERO,
+                prev_pos: Vec2::ZERO,
+                dir: Vec2::ZERO,
+                pattern: MovementPattern::Horizontal,
+                next: None,
+                anim: AnimState::Idle,
+                anim_start: 0.0,
+            },
+            enemies: Vec::new(),
+            frame_counter: 0,
+            is_player_alive: true,
+            time: 0.0,
+            death_time: None,
+            reached_goal: false,
+            golds: Vec::new(),
+            score: 0,
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+            leaderboard: Leaderboard::default(),
+        };
+        game.reset(seed);
+
+        game
+    }
+    /// Adds the levels the player can reach through doors.
+    pub fn with_levels(mut self, levels: HashMap<String, Level>) -> Self {
+        self.world.extend(levels);
+        self
+    }
+    pub fn with_leaderboard(mut self, leaderboard: Leaderboard) -> Self {
+        self.leaderboard = leaderboard;
+        self
+    }
+    /// Puts everything back how it was at the start of a run, with `seed`
+    /// driving everything random this time.
+    fn reset(&mut self, seed: u64) {
+        let start_level = self.start_level.clone();
+        self.swap_level(&start_level);
+        let player_start = *self
+            .level
+            .starts()
+            .iter()
+            .find(|(t, _)| *t == EntityType::Player)
+            .map(|(_, ploc)| ploc)
+            .expect("Start level doesn't put the player anywhere");
+        self.player = Entity {
+            pos: player_start,
+            prev_pos: player_start,
+            dir: Vec2::ZERO,
+            pattern: MovementPattern::Horizontal,
+            next: None,
+            anim: AnimState::Idle,
+            anim_start: 0.0,
+        };
+        self.frame_counter = 0;
+        self.is_player_alive = true;
+        self.time = 0.0;
+        self.death_time = None;
+        self.reached_goal = false;
+        self.score = 0;
+        self.seed = seed;
+        self.rng = StdRng::seed_from_u64(seed);
+        self.enter_level(player_start);
+    }
+    pub fn start_level(&self) -> &str {
+        &self.start_level
+    }
+    /// Seconds left on the round clock, which stops when the player dies.
+    pub fn time_left(&self) -> f32 {
+        let end = self.death_time.unwrap_or(self.time);
+        (ROUND_LENGTH - end).max(0.0)
+    }
+    /// Whether the run is over, and how.
+    pub fn ending(&self) -> Option<Ending> {
+        match self.death_time {
+            Some(death_time) if self.time - death_time >= DEATH_DELAY => Some(Ending::Lost),
+            Some(_) => None,
+            None if self.reached_goal || self.time >= ROUND_LENGTH => Some(Ending::Won),
+            None => None,
+        }
+    }
+    fn enter_level(&mut self, player_pos: Vec2) {
+        self.player.pos = player_pos;
+        // no sliding across the screen from the door we came through
+        self.player.prev_pos = player_pos;
+        self.enemies.clear();
+        self.golds.clear();
+        self.doors.clear();
+        self.chase_map = None;
+
+        for (etype, pos) in self.level.starts().iter() {
+            match etype {
+                EntityType::Door(to_level, to_x, to_y) => {
+                    self.doors.push(Door {
+                        pos: *pos,
+                        to_level: to_level.clone(),
+                        to_pos: tile_to_world(((*to_x).into(), (*to_y).into())),
+                    });
+                }
+                EntityType::Enemy => {
+                    self.enemies
+                        .push(Entity::new_enemy(*pos, MovementPattern::Horizontal));
+                }
+                EntityType::Player | EntityType::Gold => {}
+            }
+        }
+        // Don't bounce straight back if we arrived on top of a door
+        self.on_door = self.door_at_player().is_some();
+        self.spawn_enemies();
+        self.spawn_gold(GOLD_COUNT);
+    }
+
+    fn swap_level(&mut self, to_level: &str) {
+        if to_level != self.level.name() {
+            let next = self
+                .world
+                .remove(to_level)
+                .expect("Door leads to a level that wasn't loaded");
+            let prev = std::mem::replace(&mut self.level, next);
+            self.world.insert(prev.name().to_string(), prev);
+        }
+    }
+
+    fn change_level(&mut self, to_level: &str, to_pos: Vec2) {
+        self.swap_level(to_level);
+        self.enter_level(to_pos);
+    }
+
+    fn door_at_player(&self) -> Option<&Door> {
+        let middle = cell_center(self.player.pos);
+        self.doors
+            .iter()
+            .find(|door| tile_box(tile_under(door.pos)).contains_point(middle))
+    }
+
+    fn update_doors(&mut self) {
+        match self.door_at_player() {
+            None => self.on_door = false,
+            Some(_) if self.on_door => {}
+            Some(door) => {
+                let to_level = door.to_level.clone();
+                let to_pos = door.to_pos;
+                self.change_level(&to_level, to_pos);
+            }
+        }
+    }
+
+    fn spawn_gold(&mut self, gold_count: i32) {
+        let open_spaces = self.level.get_open_spaces(GOLD_AVOIDS);
+        // Filter open spaces to exclude those occupied by enemies or the player
+        let occupied: Vec<Coord> = std::iter::once(hitbox(self.player.pos, PLAYER_SIZE))
+            .chain(self.enemies.iter().map(|e| hitbox(e.pos, ENEMY_SIZE)))
+            .flat_map(|body| body.tile_rect().tiles())
+            .collect();
+        let available_spaces: Vec<_> = open_spaces
+            .into_iter()
+            .filter(|pos| !occupied.contains(pos))
+            .collect();
+
+        // Randomly choose locations from available spaces
+        for _ in 0..gold_count {
+            if let Some(&position) = available_spaces.choose(&mut self.rng) {
+                // Use `position` directly here
+                self.golds.push(tile_to_world(position));
+            }
+        }
+    }
+
+    fn spawn_enemies(&mut self) {
+        let open_spaces = self.level.get_open_spaces(TileFlags::all());
+        // remove spaces that are too close to the player
+        let open_spaces = open_spaces
+            .iter()
+            .filter(|&&pos| {
+                let pos = tile_to_world(pos);
+                let dx = (self.player.pos.x - pos.x).abs();
+                let dy = (self.player.pos.y - pos.y).abs();
+                dx > 15.0 || dy > 15.0
+            })
+            .copied()
+            .collect::<Vec<_>>();
+        let enemy_count = self.rng.gen_range(1..=2);
+
+        for _ in 0..enemy_count {
+            if let Some(&position) = open_spaces.choose(&mut self.rng) {
+                let pattern = if self.rng.gen() {
+                    MovementPattern::Horizontal
+                } else {
+                    MovementPattern::Vertical
+                };
+                let enemy = Entity::new_enemy(tile_to_world(position), pattern);
+                self.enemies.push(enemy);
+            }
+        }
+    }
+
+    fn update_gold(&mut self) {
+        let player_size = 0.25;
+        let gold_size = 0.25; // Adjust this as necessary
+
+        // everything the player passed over this step
+        let swept =
+            hitbox(self.player.prev_pos, player_size).union(&hitbox(self.player.pos, player_size));
+        // Detect golds to remove
+        let to_remove: Vec<Vec2> = self
+            .golds
+            .iter()
+            .filter_map(|gold_pos| {
+                if swept.intersects(&hitbox(*gold_pos, gold_size)) {
+                    Some(*gold_pos)
+                } else {
+                    None
+                }
+            })
+            .collect();
+
+        // Remove golds that collided
+        let before = self.golds.len();
+        self.golds.retain(|gold_pos| !to_remove.contains(gold_pos));
+        let collected = before - self.golds.len();
+
+        // Replace them somewhere else
+        self.score += collected as u32;
+        self.spawn_gold(collected as i32);
+    }
+
+    /// Walks each enemy downhill on a distance map to the player's tile.
+    /// The map is shared by all enemies and only rebuilt when the player
+    /// reaches a new tile.
+    fn move_enemies(&mut self, dt: f32) {
+        let player_tile = tile_under(self.player.pos);
+        if self.chase_map.as_ref().map(|(target, _)| *target) != Some(player_tile) {
+            let map = self.level.distance_map(&[player_tile], ENEMY_BLOCKED_BY);
+            self.chase_map = Some((player_tile, map));
+        }
+        let (_, chase_map) = self.chase_map.as_ref().unwrap();
+        for enemy in &mut self.enemies {
+            let mut step = speed_on(&self.level, enemy.pos, ENEMY_SPEED) * dt;
+            // Only pick the next tile once this one is reached
+            while let Some(next) = enemy
+                .next
+                .or_else(|| chase_map.downhill(tile_under(enemy.pos)))
+            {
+                enemy.next = Some(next);
+                let waypoint = tile_to_world(next);
+                let dist = enemy.pos.distance(waypoint);
+                if dist <= step {
+                    enemy.pos = waypoint;
+                    enemy.next = None;
+                    step -= dist;
+                } else {
+                    enemy.dir = (waypoint - enemy.pos).normalize();
+                    let body = hitbox(enemy.pos, ENEMY_SIZE);
+                    let (center, _) =
+                        move_and_collide(&self.level, body, enemy.dir * step, ENEMY_BLOCKED_BY);
+                    enemy.pos += center - body.center;
+                    break;
+                }
+            }
+        }
+        // Enemies chasing down the same map would pile up on one spot, so
+        // each is pushed out of any it has caught up with
+        for i in 0..self.enemies.len() {
+            for j in 0..i {
+                let body = hitbox(self.enemies[i].pos, ENEMY_SIZE);
+                let other = hitbox(self.enemies[j].pos, ENEMY_SIZE);
+                if let Some(push) = body.overlap(&other) {
+                    let (center, _) = move_and_collide(&self.level, body, push, ENEMY_BLOCKED_BY);
+                    self.enemies[i].pos += center - body.center;
+                }
+            }
+        }
+    }
+
+    /// Moves the player by `delta`, sliding along any walls in the way.
+    fn move_player(&mut self, delta: Vec2) {
+        let player = hitbox(self.player.pos, PLAYER_SIZE);
+        let (center, _) = move_and_collide(&self.level, player, delta, PLAYER_BLOCKED_BY);
+        self.player.pos += center - player.center;
+    }
+
+    pub fn outcome(&self) -> Outcome {
+        Outcome {
+            score: self.score,
+            alive: self.is_player_alive,
+            level: self.level.name().to_string(),
+            player: (self.player.pos.x, self.player.pos.y),
+        }
+    }
+
+    /// Advances whichever screen the game is on by one step.  `controls`
+    /// steer the player; menus read `input` directly.
+    pub fn update(&mut self, input: &Input, controls: Controls, dt: f32) -> Option<GameEvent> {
+        let pressed = |keys: &[Key]| keys.iter().any(|&k| input.is_key_pressed(k));
+        match &mut self.state {
+            GameState::Title => {
+                if pressed(&[Key::Escape]) {
+                    return Some(GameEvent::Quit);
+                }
+                if pressed(&[Key::Enter, Key::Space]) {
+                    self.state = GameState::Playing;
+                    return Some(GameEvent::RunStarted);
+                }
+            }
+            GameState::Playing => {
+                // simulate before pausing so every step `is_running` was
+                // true for gets simulated, which replays rely on
+                self.simulate(controls, dt);
+                if !self.is_player_alive {
+                    self.state = GameState::Dead;
+                } else if self.ending() == Some(Ending::Won) {
+                    self.state = GameState::Won(InitialsEntry::default());
+                    return Some(GameEvent::RunEnded(Ending::Won));
+                } else if pressed(&[Key::Escape, Key::KeyP]) {
+                    self.state = GameState::Paused;
+                }
+            }
+            GameState::Paused => {
+                if pressed(&[Key::Escape, Key::KeyP]) {
+                    self.state = GameState::Playing;
+                }
+            }
+            GameState::Dead => {
+                self.simulate(controls, dt);
+                if self.ending() == Some(Ending::Lost) {
+                    self.state = GameState::Leaderboard;
+                    return Some(GameEvent::RunEnded(Ending::Lost));
+                }
+            }
+            GameState::Won(entry) => {
+                if entry.update(input) {
+                    let entry = Entry {
+                        initials: entry.initials(),
+                        score: self.score,
+                        level: self.start_level.clone(),
+                        seed: Some(self.seed),
+                        duration: self.time,
+                        timestamp: None,
+                    };
+                    self.leaderboard.add(entry.clone());
+                    self.state = GameState::Leaderboard;
+                    return Some(GameEvent::Scored(entry));
+                }
+            }
+            GameState::Leaderboard => {
+                if pressed(&[Key::Enter, Key::Space]) {
+                    // the same seed as before, so `--seed`, the level's
+                    // seed and the one printed at startup all still hold
+                    self.reset(self.seed);
+                    self.state = GameState::Title;
+                }
+            }
+        }
+        None
+    }
+
+    pub fn simulate(&mut self, controls: Controls, dt: f32) {
+        self.time += dt;
+        self.player.prev_pos = self.player.pos;
+        for enemy in &mut self.enemies {
+            enemy.prev_pos = enemy.pos;
+        }
+        if self.is_player_alive {
+            let speed = speed_on(&self.level, self.player.pos, 5.0);
+
+            let axes = Vec2::new(controls.x_axis(), controls.y_axis());
+            self.move_player(axes * (speed * dt));
+
+            self.frame_counter += 1;
+            self.move_enemies(dt);
+            self.frame_counter = 0;
+
+            self.update_gold();
+            self.update_doors();
+
+            let player = hitbox(self.player.prev_pos, PLAYER_SIZE);
+            let moved = self.player.pos - self.player.prev_pos;
+            // measured from the enemy, so a player and an enemy passing
+            // through each other in one step still meet
+            let caught = self.enemies.iter().any(|enemy| {
+                let body = hitbox(enemy.prev_pos, ENEMY_SIZE);
+                let relative = moved - (enemy.pos - enemy.prev_pos);
+                player.intersects(&body) || player.sweep(relative, &body).is_some()
+            });
+            let flags = flags_under(&self.level, self.player.pos);
+            if caught || flags.contains(TileFlags::DAMAGING) {
+                self.is_player_alive = false;
+                self.death_time = Some(self.time);
+            } else if flags.contains(TileFlags::GOAL) {
+                self.reached_goal = true;
+            }
+        }
+        let time = self.time;
+        let player_anim = if self.is_player_alive {
+            AnimState::moving(self.player.pos - self.player.prev_pos)
+        } else {
+            AnimState::Death
+        };
+        self.player.animate(player_anim, time);
+        for enemy in &mut self.enemies {
+            enemy.animate(AnimState::moving(enemy.pos - enemy.prev_pos), time);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    const ROOM: &str = "room 6 5
+===
+# s 0 0 16 16
+. o 0 0 16 16
+===
+# # # # # #
+# . . . . #
+# . . . . #
+# . . . . #
+# # # # # #
+===
+player 1 1
+";
+    fn game(level: &str) -> Game {
+        Game::new(Level::from_str(level).unwrap(), 1)
+    }
+    fn run(game: &mut Game, bits: u8, steps: usize) {
+        for _ in 0..steps {
+            game.simulate(Controls::from_bits(bits), 1.0 / 50.0);
+        }
+    }
+    #[test]
+    fn test_walls_stop_player() {
+        let mut game = game(ROOM);
+        run(&mut game, Controls::LEFT | Controls::UP, 100);
+        assert!(game.is_player_alive);
+        // the player's box rests against the walls' inner faces
+        let (x, y) = (game.player.pos.x, game.player.pos.y);
+        assert!((x + 0.5 - PLAYER_SIZE / 2.0 - 1.0).abs() < 1e-4, "{x}");
+        assert!((y + 0.5 - PLAYER_SIZE / 2.0 - 1.0).abs() < 1e-4, "{y}");
+        run(&mut game, Controls::RIGHT | Controls::DOWN, 200);
+        let (x, y) = (game.player.pos.x, game.player.pos.y);
+        assert!((x + 0.5 + PLAYER_SIZE / 2.0 - 5.0).abs() < 1e-4, "{x}");
+        assert!((y + 0.5 + PLAYER_SIZE / 2.0 - 4.0).abs() < 1e-4, "{y}");
+    }
+    #[test]
+    fn test_enemy_contact_ends_run() {
+        let mut game = game(&format!("{ROOM}enemy 4 3\n"));
+        assert_eq!(game.enemies.len(), 1);
+        run(&mut game, 0, 150);
+        assert!(!game.is_player_alive);
+        assert_eq!(game.ending(), None);
+        run(&mut game, 0, (DEATH_DELAY * 50.0) as usize);
+        assert_eq!(game.ending(), Some(Ending::Lost));
+    }
+    #[test]
+    fn test_enemies_keep_apart() {
+        // starting on the same tile, they spread out before moving on
+        let mut game = game(&format!("{ROOM}enemy 4 3\nenemy 4 3\n"));
+        while game.is_player_alive {
+            run(&mut game, 0, 1);
+            let [a, b] = &game.enemies[..] else {
+                panic!("expected two enemies");
+            };
+            assert!(!hitbox(a.pos, ENEMY_SIZE).intersects(&hitbox(b.pos, ENEMY_SIZE)));
+        }
+    }
+    #[test]
+    fn test_surviving_wins() {
+        let mut game = game(ROOM);
+        run(&mut game, 0, (ROUND_LENGTH * 50.0) as usize + 1);
+        assert_eq!(game.ending(), Some(Ending::Won));
+    }
+    #[test]
+    fn test_door_changes_level() {
+        let other = ROOM.replace("room", "other").replace("player 1 1\n", "");
+        let mut levels = HashMap::new();
+        levels.insert("other".to_string(), Level::from_str(&other).unwrap());
+        let mut game = game(&format!("{ROOM}door other 2 3 3 1\n")).with_levels(levels);
+        for _ in 0..50 {
+            run(&mut game, Controls::RIGHT, 1);
+            if game.level.name() != "room" {
+                break;
+            }
+        }
+        assert_eq!(game.level.name(), "other");
+        assert_eq!(game.player.pos, Vec2 { x: 2.0, y: 3.0 });
+        assert!(game.doors.is_empty());
+    }
+    #[test]
+    fn test_tile_flags() {
+        const MARSH: &str = "marsh 6 4
+===
+# s 0 0 16 16
+. o 0 0 16 16
+~ m 0 0 16 16
+^ d 0 0 16 16
+* g 0 0 16 16
+===
+# # # # # #
+# . ~ ~ ^ #
+# . . . * #
+# # # # # #
+===
+player 1 1
+";
+        // mud slows the player down
+        let mut dry = game(&MARSH.replace("~ m", "~ o"));
+        let mut muddy = game(MARSH);
+        run(&mut dry, Controls::RIGHT, 15);
+        run(&mut muddy, Controls::RIGHT, 15);
+        assert!(dry.is_player_alive && muddy.is_player_alive);
+        assert!(muddy.player.pos.x < dry.player.pos.x - 0.2);
+        // spikes kill
+        run(&mut muddy, Controls::RIGHT, 50);
+        assert!(!muddy.is_player_alive);
+        // and the goal wins straight away
+        let mut game = game(MARSH);
+        run(&mut game, Controls::DOWN, 10);
+        run(&mut game, Controls::RIGHT, 50);
+        assert!(game.is_player_alive);
+        assert_eq!(game.ending(), Some(Ending::Won));
+    }
+    #[test]
+    fn test_gold_on_any_safe_floor() {
+        // nothing but mud to stand on
+        let bog = ROOM.replace(". o", ". m");
+        let mut game = game(&bog);
+        assert_eq!(game.golds.len(), GOLD_COUNT as usize);
+        assert!(game
+            .golds
+            .iter()
+            .all(|&gold| flags_under(&game.level, gold) == TileFlags::SLOW));
+        // standing still picks nothing up and scores nothing
+        let first = game.golds.clone();
+        run(&mut game, 0, 20);
+        assert_eq!(game.score, 0);
+        assert_eq!(game.golds, first);
+        // walking over some scores them, and they're put back elsewhere
+        run(&mut game, Controls::RIGHT | Controls::DOWN, 40);
+        assert!(game.score > 0);
+        assert_ne!(game.golds, first);
+        assert_eq!(game.golds.len(), GOLD_COUNT as usize);
+    }
+    #[test]
+    fn test_animation_states() {
+        let mut game = game(&format!("{ROOM}enemy 4 3\n"));
+        run(&mut game, Controls::RIGHT, 5);
+        assert_eq!(game.player.anim, AnimState::WalkRight);
+        assert_eq!(game.player.anim_start, 1.0 / 50.0);
+        assert_eq!(game.enemies[0].anim, AnimState::WalkLeft);
+        // walking into the wall is standing still
+        run(&mut game, Controls::UP, 50);
+        assert_eq!(game.player.anim, AnimState::Idle);
+        run(&mut game, Controls::DOWN | Controls::RIGHT, 200);
+        assert!(!game.is_player_alive);
+        assert_eq!(game.player.anim, AnimState::Death);
+        assert_eq!(Some(game.player.anim_start), game.death_time);
+    }
+    #[test]
+    fn test_same_seed_same_run() {
+        let mut a = game(&format!("{ROOM}enemy 4 3\n"));
+        let mut b = game(&format!("{ROOM}enemy 4 3\n"));
+        assert_eq!(a.golds, b.golds);
+        for bits in [
+            Controls::RIGHT,
+            Controls::DOWN,
+            Controls::LEFT | Controls::UP,
+        ] {
+            run(&mut a, bits, 20);
+            run(&mut b, bits, 20);
+        }
+        assert_eq!(a.outcome(), b.outcome());
+    }
+    #[test]
+    fn test_pause_stops_clock() {
+        let mut game = game(ROOM);
+        let input = Input::default();
+        assert_eq!(game.update(&input, Controls::default(), 0.02), None);
+        assert!(matches!(game.state, GameState::Title));
+        game.state = GameState::Paused;
+        for _ in 0..10 {
+            game.update(&input, Controls::from_bits(Controls::RIGHT), 0.02);
+        }
+        assert_eq!(game.time, 0.0);
+        assert_eq!(game.player.pos, Vec2 { x: 1.0, y: 1.0 });
+        game.state = GameState::Playing;
+        game.update(&input, Controls::from_bits(Controls::RIGHT), 0.02);
+        assert!(game.time > 0.0);
+    }
+    #[test]
+    fn test_death_shows_leaderboard() {
+        let mut game = game(&format!("{ROOM}enemy 4 3\n"));
+        let input = Input::default();
+        game.state = GameState::Playing;
+        let mut events = vec![];
+        for _ in 0..500 {
+            events.extend(game.update(&input, Controls::default(), 0.02));
+        }
+        assert_eq!(events, vec![GameEvent::RunEnded(Ending::Lost)]);
+        assert!(matches!(game.state, GameState::Leaderboard));
+        assert!(game.time < 10.0);
+    }
+}
@@ -1,8 +1,119 @@
-#[derive(Clone, Copy, PartialEq, Debug)]
+use crate::grid::Coord;
+use crate::TILE_SZ;
+
+/*
+World space is measured in tiles, with x growing to the right and y
+growing downwards from the top-left corner of the level, the same way
+level files and grids are laid out.  Tile (x, y) covers the square from
+(x, y) to (x + 1, y + 1).
+
+Entities are positioned by the top-left corner of the tile-sized cell
+they're drawn in, so an entity at (x, y) for whole numbers x and y sits
+exactly on tile (x, y), and its middle is half a tile right and down of
+its position.
+
+Screen space is frenderer's: pixels, with y growing upwards from the
+bottom of the level.  Only drawing and the camera should need it.
+*/
+
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
 pub struct Vec2 {
     pub x: f32,
     pub y: f32,
 }
+
+impl Vec2 {
+    pub const ZERO: Vec2 = Vec2 { x: 0.0, y: 0.0 };
+
+    pub fn new(x: f32, y: f32) -> Self {
+        Self { x, y }
+    }
+    pub fn dot(self, other: Vec2) -> f32 {
+        self.x * other.x + self.y * other.y
+    }
+    pub fn length(self) -> f32 {
+        self.dot(self).sqrt()
+    }
+    pub fn distance(self, other: Vec2) -> f32 {
+        (other - self).length()
+    }
+    /// This vector scaled to length 1, or zero if it has no length.
+    pub fn normalize(self) -> Vec2 {
+        let len = self.length();
+        if len == 0.0 {
+            Vec2::ZERO
+        } else {
+            Vec2::new(self.x / len, self.y / len)
+        }
+    }
+    /// The point `t` of the way from `self` to `other`.
+    pub fn lerp(self, other: Vec2, t: f32) -> Vec2 {
+        self + (other - self) * t
+    }
+    /// -1, 0 or 1 on each axis; unlike `f32::signum`, zero stays zero.
+    pub fn signum(self) -> Vec2 {
+        let sign = |v: f32| if v == 0.0 { 0.0 } else { v.signum() };
+        Vec2::new(sign(self.x), sign(self.y))
+    }
+    /// The nearest whole-numbered point, if neither axis is negative.
+    pub fn round_to_coord(self) -> Option<Coord> {
+        let (x, y) = (self.x.round(), self.y.round());
+        (x >= 0.0 && y >= 0.0).then_some((x as usize, y as usize))
+    }
+}
+
+impl std::ops::Add for Vec2 {
+    type Output = Vec2;
+    fn add(self, other: Vec2) -> Vec2 {
+        Vec2::new(self.x + other.x, self.y + other.y)
+    }
+}
+
+impl std::ops::AddAssign for Vec2 {
+    fn add_assign(&mut self, other: Vec2) {
+        *self = *self + other;
+    }
+}
+
+impl std::ops::Sub for Vec2 {
+    type Output = Vec2;
+    fn sub(self, other: Vec2) -> Vec2 {
+        Vec2::new(self.x - other.x, self.y - other.y)
+    }
+}
+
+impl std::ops::Mul<f32> for Vec2 {
+    type Output = Vec2;
+    fn mul(self, scale: f32) -> Vec2 {
+        Vec2::new(self.x * scale, self.y * scale)
+    }
+}
+
+impl std::ops::Neg for Vec2 {
+    type Output = Vec2;
+    fn neg(self) -> Vec2 {
+        Vec2::new(-self.x, -self.y)
+    }
+}
+
+impl From<Coord> for Vec2 {
+    fn from((x, y): Coord) -> Self {
+        Vec2::new(x as f32, y as f32)
+    }
+}
+
+impl From<[f32; 2]> for Vec2 {
+    fn from([x, y]: [f32; 2]) -> Self {
+        Vec2::new(x, y)
+    }
+}
+
+impl From<Vec2> for [f32; 2] {
+    fn from(v: Vec2) -> Self {
+        [v.x, v.y]
+    }
+}
+/// A block of whole tiles.
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub struct Rect {
     pub x: u16,
@@ -10,3 +121,324 @@ pub struct Rect {
     pub w: u16,
     pub h: u16,
 }
+
+impl Rect {
+    /// Every tile in the block, row by row.
+    pub fn tiles(self) -> impl Iterator<Item = Coord> {
+        let (x, y) = (self.x as usize, self.y as usize);
+        (y..y + self.h as usize)
+            .flat_map(move |ty| (x..x + self.w as usize).map(move |tx| (tx, ty)))
+    }
+}
+
+/// An axis-aligned box in world space, given by its middle and half its
+/// size on each axis.  Boxes that only touch along an edge don't count as
+/// intersecting.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Aabb {
+    pub center: Vec2,
+    pub half: Vec2,
+}
+
+/// Where a moving box first touches another one: how far along its
+/// movement, from 0 to 1, and the face of the other box it meets.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Sweep {
+    pub time: f32,
+    // points out of the face that was hit
+    pub normal: Vec2,
+}
+
+impl Aabb {
+    pub fn new(center: Vec2, size: Vec2) -> Self {
+        Self {
+            center,
+            half: size * 0.5,
+        }
+    }
+    /// A `size` by `size` box around `center`.
+    pub fn square(center: Vec2, size: f32) -> Self {
+        Self::new(center, Vec2::new(size, size))
+    }
+    /// The box from `min` to `max`.
+    pub fn from_corners(min: Vec2, max: Vec2) -> Self {
+        Self::new(min.lerp(max, 0.5), max - min)
+    }
+    pub fn min(&self) -> Vec2 {
+        self.center - self.half
+    }
+    pub fn max(&self) -> Vec2 {
+        self.center + self.half
+    }
+    pub fn intersects(&self, other: &Aabb) -> bool {
+        let gap = other.center - self.center;
+        gap.x.abs() < self.half.x + other.half.x && gap.y.abs() < self.half.y + other.half.y
+    }
+    /// The shortest move that takes this box out of `other`, if they
+    /// intersect.  It's along whichever axis they overlap least on.
+    pub fn overlap(&self, other: &Aabb) -> Option<Vec2> {
+        if !self.intersects(other) {
+            return None;
+        }
+        let gap = self.center - other.center;
+        let depth_x = self.half.x + other.half.x - gap.x.abs();
+        let depth_y = self.half.y + other.half.y - gap.y.abs();
+        // boxes with the same middle get pushed up and left
+        let sign = |v: f32| if v > 0.0 { 1.0 } else { -1.0 };
+        Some(if depth_x < depth_y {
+            Vec2::new(depth_x * sign(gap.x), 0.0)
+        } else {
+            Vec2::new(0.0, depth_y * sign(gap.y))
+        })
+    }
+    /// Whether `point` is inside the box or on its top or left edge, so
+    /// neighbouring boxes never both contain a point.
+    pub fn contains_point(&self, point: Vec2) -> bool {
+        let (min, max) = (self.min(), self.max());
+        min.x <= point.x && point.x < max.x && min.y <= point.y && point.y < max.y
+    }
+    /// The smallest box containing both boxes.
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        let (a_min, a_max, b_min, b_max) = (self.min(), self.max(), other.min(), other.max());
+        Aabb::from_corners(
+            Vec2::new(a_min.x.min(b_min.x), a_min.y.min(b_min.y)),
+            Vec2::new(a_max.x.max(b_max.x), a_max.y.max(b_max.y)),
+        )
+    }
+    /// This box grown by `amount` on every side.
+    pub fn expand(&self, amount: Vec2) -> Aabb {
+        Aabb {
+            center: self.center,
+            half: self.half + amount,
+        }
+    }
+    /// When this box, moved by `delta`, first touches `other`.  Boxes that
+    /// already intersect, or that only slide along each other, never hit.
+    pub fn sweep(&self, delta: Vec2, other: &Aabb) -> Option<Sweep> {
+        if self.intersects(other) {
+            return None;
+        }
+        // shrinking this box to a point and growing the other one to
+        // match turns it into a ray cast
+        let target = other.expand(self.half);
+        let (min, max) = (target.min(), target.max());
+        let axis = |from: f32, d: f32, lo: f32, hi: f32| {
+            if d == 0.0 {
+                if lo < from && from < hi {
+                    Some((f32::NEG_INFINITY, f32::INFINITY))
+                } else {
+                    None
+                }
+            } else {
+                let (t0, t1) = ((lo - from) / d, (hi - from) / d);
+                Some((t0.min(t1), t0.max(t1)))
+            }
+        };
+        let (x_enter, x_exit) = axis(self.center.x, delta.x, min.x, max.x)?;
+        let (y_enter, y_exit) = axis(self.center.y, delta.y, min.y, max.y)?;
+        let enter = x_enter.max(y_enter);
+        let exit = x_exit.min(y_exit);
+        if enter >= exit || !(0.0..=1.0).contains(&enter) {
+            return None;
+        }
+        let normal = if x_enter > y_enter {
+            Vec2::new(-delta.x.signum(), 0.0)
+        } else {
+            Vec2::new(0.0, -delta.y.signum())
+        };
+        Some(Sweep {
+            time: enter,
+            normal,
+        })
+    }
+    /// The tiles this box covers any part of, leaving out any above or
+    /// left of the level.
+    pub fn tile_rect(&self) -> Rect {
+        let (min, max) = (self.min(), self.max());
+        let x0 = min.x.floor().max(0.0);
+        let y0 = min.y.floor().max(0.0);
+        Rect {
+            x: x0 as u16,
+            y: y0 as u16,
+            w: (max.x.ceil() - x0).max(0.0) as u16,
+            h: (max.y.ceil() - y0).max(0.0) as u16,
+        }
+    }
+}
+
+impl From<Rect> for Aabb {
+    fn from(rect: Rect) -> Self {
+        let min = Vec2::new(rect.x.into(), rect.y.into());
+        Aabb::from_corners(min, min + Vec2::new(rect.w.into(), rect.h.into()))
+    }
+}
+
+/// The world position of tile `tile`'s top-left corner, which is where an
+/// entity standing exactly on it is placed.
+pub fn tile_to_world(tile: Coord) -> Vec2 {
+    Vec2::from(tile)
+}
+
+/// The tile containing the world point `pos`, if it isn't above or left
+/// of the level.  Whether it's inside the level depends on the level.
+pub fn world_to_tile(pos: Vec2) -> Option<Coord> {
+    if pos.x < 0.0 || pos.y < 0.0 {
+        return None;
+    }
+    Some((pos.x.floor() as usize, pos.y.floor() as usize))
+}
+
+/// The middle of the tile-sized cell an entity at `pos` is drawn in.
+pub fn cell_center(pos: Vec2) -> Vec2 {
+    pos + Vec2::new(0.5, 0.5)
+}
+
+/// Where the world point `pos` appears in frenderer's sprite space, for a
+/// level `grid_height` tiles tall.
+pub fn world_to_screen(pos: Vec2, grid_height: usize) -> [f32; 2] {
+    [
+        pos.x * TILE_SZ as f32,
+        (grid_height as f32 - pos.y) * TILE_SZ as f32,
+    ]
+}
+
+/// The world point drawn at `screen` in a level `grid_height` tiles tall.
+#[allow(dead_code)]
+pub fn screen_to_world(screen: [f32; 2], grid_height: usize) -> Vec2 {
+    Vec2::new(
+        screen[0] / TILE_SZ as f32,
+        grid_height as f32 - screen[1] / TILE_SZ as f32,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_vec2_ops() {
+        let a = Vec2::new(3.0, 4.0);
+        let b = Vec2::new(1.0, -2.0);
+        assert_eq!(a + b, Vec2::new(4.0, 2.0));
+        assert_eq!(a - b, Vec2::new(2.0, 6.0));
+        assert_eq!(a * 0.5, Vec2::new(1.5, 2.0));
+        assert_eq!(-b, Vec2::new(-1.0, 2.0));
+        let mut c = a;
+        c += b;
+        assert_eq!(c, a + b);
+        assert_eq!(Vec2::default(), Vec2::ZERO);
+    }
+    #[test]
+    fn test_vec2_math() {
+        let a = Vec2::new(3.0, 4.0);
+        assert_eq!(a.length(), 5.0);
+        assert_eq!(a.dot(Vec2::new(2.0, -1.0)), 2.0);
+        assert_eq!(a.distance(Vec2::new(0.0, 8.0)), 5.0);
+        assert_eq!(a.normalize(), Vec2::new(0.6, 0.8));
+        assert_eq!(Vec2::ZERO.normalize(), Vec2::ZERO);
+        assert_eq!(a.lerp(Vec2::new(5.0, 0.0), 0.5), Vec2::new(4.0, 2.0));
+        assert_eq!(a.lerp(Vec2::ZERO, 0.0), a);
+        assert_eq!(Vec2::new(-2.5, 0.0).signum(), Vec2::new(-1.0, 0.0));
+    }
+    #[test]
+    fn test_vec2_conversions() {
+        assert_eq!(Vec2::new(2.4, 6.6).round_to_coord(), Some((2, 7)));
+        assert_eq!(Vec2::new(-0.4, 1.0).round_to_coord(), Some((0, 1)));
+        assert_eq!(Vec2::new(-0.6, 1.0).round_to_coord(), None);
+        assert_eq!(Vec2::from((3, 9)), Vec2::new(3.0, 9.0));
+        assert_eq!(Vec2::from([1.5, 2.0]), Vec2::new(1.5, 2.0));
+        assert_eq!(<[f32; 2]>::from(Vec2::new(1.5, 2.0)), [1.5, 2.0]);
+    }
+    #[test]
+    fn test_aabb() {
+        let a = Aabb::square(Vec2::new(1.0, 1.0), 2.0);
+        let b = Aabb::square(Vec2::new(2.25, 1.5), 1.0);
+        assert!(a.intersects(&b) && b.intersects(&a));
+        assert_eq!(a.overlap(&b), Some(Vec2::new(-0.25, 0.0)));
+        assert_eq!(b.overlap(&a), Some(Vec2::new(0.25, 0.0)));
+        // touching edges don't count
+        let c = Aabb::square(Vec2::new(3.0, 1.0), 2.0);
+        assert!(!a.intersects(&c));
+        assert_eq!(a.overlap(&c), None);
+        assert!(a.contains_point(Vec2::ZERO));
+        assert!(!a.contains_point(Vec2::new(2.0, 1.0)));
+        assert!(c.contains_point(Vec2::new(2.0, 1.0)));
+        let u = a.union(&b);
+        assert_eq!((u.min(), u.max()), (Vec2::ZERO, Vec2::new(2.75, 2.0)));
+        let e = b.expand(Vec2::new(0.5, 1.0));
+        assert_eq!(
+            (e.min(), e.max()),
+            (Vec2::new(1.25, 0.0), Vec2::new(3.25, 3.0))
+        );
+    }
+    #[test]
+    fn test_aabb_sweep() {
+        let wall = Aabb::square(Vec2::new(5.0, 0.5), 1.0);
+        let mover = Aabb::square(Vec2::new(0.5, 0.5), 1.0);
+        let hit = mover.sweep(Vec2::new(7.0, 0.0), &wall).unwrap();
+        assert_eq!(hit.time, 0.5);
+        assert_eq!(hit.normal, Vec2::new(-1.0, 0.0));
+        // too short, wrong way, passing by, or sliding along the face
+        assert_eq!(mover.sweep(Vec2::new(3.0, 0.0), &wall), None);
+        assert_eq!(mover.sweep(Vec2::new(-8.0, 0.0), &wall), None);
+        assert_eq!(mover.sweep(Vec2::new(8.0, 4.0), &wall), None);
+        let below = Aabb::square(Vec2::new(5.0, 1.5), 1.0);
+        assert_eq!(below.sweep(Vec2::new(-8.0, 0.0), &wall), None);
+        // diagonal into the top face
+        let above = Aabb::square(Vec2::new(3.5, -2.5), 1.0);
+        let hit = above.sweep(Vec2::new(2.0, 4.0), &wall).unwrap();
+        assert_eq!(hit.time, 0.5);
+        assert_eq!(hit.normal, Vec2::new(0.0, -1.0));
+    }
+    #[test]
+    fn test_aabb_tiles() {
+        let rect = Rect {
+            x: 2,
+            y: 3,
+            w: 2,
+            h: 1,
+        };
+        let aabb = Aabb::from(rect);
+        assert_eq!(
+            (aabb.min(), aabb.max()),
+            (Vec2::new(2.0, 3.0), Vec2::new(4.0, 4.0))
+        );
+        assert_eq!(aabb.tile_rect(), rect);
+        assert_eq!(rect.tiles().collect::<Vec<_>>(), vec![(2, 3), (3, 3)]);
+        let player = Aabb::square(Vec2::new(1.5, 0.25), 0.5);
+        assert_eq!(
+            player.tile_rect(),
+            Rect {
+                x: 1,
+                y: 0,
+                w: 1,
+                h: 1
+            }
+        );
+        let straddling = Aabb::square(Vec2::new(2.0, 2.0), 0.5);
+        assert_eq!(
+            straddling.tile_rect(),
+            Rect {
+                x: 1,
+                y: 1,
+                w: 2,
+                h: 2
+            }
+        );
+    }
+    #[test]
+    fn test_conversions() {
+        let pos = Vec2 { x: 2.75, y: 0.5 };
+        assert_eq!(world_to_tile(pos), Some((2, 0)));
+        assert_eq!(world_to_tile(Vec2 { x: -0.1, y: 3.0 }), None);
+        assert_eq!(world_to_tile(tile_to_world((4, 7))), Some((4, 7)));
+        assert_eq!(
+            world_to_tile(cell_center(tile_to_world((4, 7)))),
+            Some((4, 7))
+        );
+        // the top row of a 15 tile tall level is at the top of the screen
+        let top_left = world_to_screen(tile_to_world((0, 0)), 15);
+        assert_eq!(top_left, [0.0, 15.0 * TILE_SZ as f32]);
+        assert_eq!(world_to_screen(tile_to_world((0, 15)), 15), [0.0, 0.0]);
+        assert_eq!(screen_to_world(world_to_screen(pos, 15), 15), pos);
+    }
+}
@@ -122,6 +122,127 @@ impl<T> Grid<T> {
         }
         neighbors.into_iter()
     }
+    /// Finds a cheapest path from `start` to `goal`, both included.
+    /// `cost` gives the price of stepping onto a cell, or `None` if it
+    /// can't be entered; costs should be at least 1 so the distance
+    /// heuristic never overestimates.  With `diagonal` set, paths may
+    /// also move between corner-touching cells.
+    pub fn astar(
+        &self,
+        start: Coord,
+        goal: Coord,
+        cost: impl Fn(Coord, &T) -> Option<u32>,
+        diagonal: bool,
+    ) -> Option<Vec<Coord>> {
+        use std::cmp::Reverse;
+        use std::collections::BinaryHeap;
+        let start_idx = self.coord_to_index(start)?;
+        let goal_idx = self.coord_to_index(goal)?;
+        let heuristic = |(x, y): Coord| -> u32 {
+            let dx = x.abs_diff(goal.0) as u32;
+            let dy = y.abs_diff(goal.1) as u32;
+            if diagonal {
+                dx.max(dy)
+            } else {
+                dx + dy
+            }
+        };
+        let mut best = vec![u32::MAX; self.storage.len()];
+        let mut came_from: Vec<Option<usize>> = vec![None; self.storage.len()];
+        let mut open = BinaryHeap::new();
+        best[start_idx] = 0;
+        open.push(Reverse((heuristic(start), 0, start_idx)));
+        while let Some(Reverse((_, so_far, idx))) = open.pop() {
+            if idx == goal_idx {
+                let mut path = vec![goal];
+                let mut here = idx;
+                while let Some(prev) = came_from[here] {
+                    path.push(self.index_to_coord(prev).unwrap());
+                    here = prev;
+                }
+                path.reverse();
+                return Some(path);
+            }
+            if so_far > best[idx] {
+                // a cheaper way here was already expanded
+                continue;
+            }
+            let (x, y) = self.index_to_coord(idx).unwrap();
+            let neighbors: Vec<Coord> = if diagonal {
+                self.neighbors_8(x, y).collect()
+            } else {
+                self.neighbors_4(x, y).collect()
+            };
+            for next in neighbors {
+                let next_idx = self.coord_to_index(next).unwrap();
+                let Some(step) = cost(next, &self.storage[next_idx]) else {
+                    continue;
+                };
+                let through = so_far.saturating_add(step);
+                if through < best[next_idx] {
+                    best[next_idx] = through;
+                    came_from[next_idx] = Some(idx);
+                    open.push(Reverse((through + heuristic(next), through, next_idx)));
+                }
+            }
+        }
+        None
+    }
+    /// How many steps each cell is from the nearest of `sources`,
+    /// moving between edge-adjacent cells that are `passable`.  Cells no
+    /// source can reach are `None`.  Pair it with
+    /// [`downhill`](Grid::downhill) to send any number of agents toward
+    /// the sources from a single search.
+    pub fn dijkstra_map(
+        &self,
+        sources: &[Coord],
+        passable: impl Fn(Coord, &T) -> bool,
+    ) -> Grid<Option<u32>> {
+        let mut dists = Grid::new(self.width, self.height, vec![None; self.storage.len()]);
+        let mut frontier = std::collections::VecDeque::new();
+        for &source in sources {
+            if let Some(dist @ None) = dists.get_mut(source.0, source.1) {
+                *dist = Some(0);
+                frontier.push_back(source);
+            }
+        }
+        // Every step costs the same, so visiting cells breadth-first
+        // settles each one at its shortest distance.
+        while let Some((x, y)) = frontier.pop_front() {
+            let next_dist = dists[(x, y)].unwrap() + 1;
+            for next in self.neighbors_4(x, y) {
+                if dists[next].is_none() && passable(next, &self[next]) {
+                    dists[next] = Some(next_dist);
+                    frontier.push_back(next);
+                }
+            }
+        }
+        dists
+    }
+}
+
+impl Grid<Option<u32>> {
+    /// The edge-adjacent neighbor of `(x, y)` closest to a source in this
+    /// distance map, if it is closer than `(x, y)` itself.
+    pub fn downhill(&self, (x, y): Coord) -> Option<Coord> {
+        let here = self.get(x, y)?.unwrap_or(u32::MAX);
+        self.neighbors_4(x, y)
+            .filter_map(|n| self[n].map(|d| (d, n)))
+            .filter(|&(d, _)| d < here)
+            .min()
+            .map(|(_, n)| n)
+    }
+    /// The reachable edge-adjacent neighbor of `(x, y)` farthest from
+    /// every source, if it is farther than `(x, y)`; useful for fleeing.
+    #[allow(dead_code)]
+    pub fn uphill(&self, (x, y): Coord) -> Option<Coord> {
+        let here = (*self.get(x, y)?)?;
+        self.neighbors_4(x, y)
+            .filter_map(|n| self[n].map(|d| (d, n)))
+            .filter(|&(d, _)| d > here)
+            .max()
+            .map(|(_, n)| n)
+    }
 }
 
 impl<T> std::ops::Index<usize> for Grid<T> {
@@ -176,6 +297,101 @@ mod tests {
             assert!(y <= 32);
         }
     }
+    // '#' is a wall, anything else costs its digit (or 1)
+    fn maze(rows: &[&str]) -> Grid<char> {
+        Grid::new(
+            rows[0].len(),
+            rows.len(),
+            rows.iter().flat_map(|r| r.chars()).collect::<Vec<_>>(),
+        )
+    }
+    fn maze_cost(_: Coord, c: &char) -> Option<u32> {
+        match c {
+            '#' => None,
+            c => Some(c.to_digit(10).unwrap_or(1)),
+        }
+    }
+    #[test]
+    fn test_astar() {
+        let grid = maze(&[
+            ".....", //
+            ".###.", //
+            "...#.", //
+            "##.#.", //
+            ".....",
+        ]);
+        let path = grid.astar((0, 0), (0, 4), maze_cost, false).unwrap();
+        assert_eq!(path.first(), Some(&(0, 0)));
+        assert_eq!(path.last(), Some(&(0, 4)));
+        assert_eq!(path.len(), 9);
+        for pair in path.windows(2) {
+            let ((ax, ay), (bx, by)) = (pair[0], pair[1]);
+            assert_eq!(ax.abs_diff(bx) + ay.abs_diff(by), 1);
+            assert_ne!(grid[pair[1]], '#');
+        }
+        assert_eq!(
+            grid.astar((2, 2), (2, 2), maze_cost, false),
+            Some(vec![(2, 2)])
+        );
+        // diagonal steps cut the corner at (1, 2) -> (2, 3)
+        let path = grid.astar((0, 2), (2, 4), maze_cost, true).unwrap();
+        assert_eq!(path, vec![(0, 2), (1, 2), (2, 3), (2, 4)]);
+        assert_eq!(
+            grid.astar((0, 2), (2, 4), maze_cost, false).unwrap().len(),
+            5
+        );
+    }
+    #[test]
+    fn test_astar_costs_and_walls() {
+        let grid = maze(&[
+            "...", //
+            ".9.", //
+            "...",
+        ]);
+        // going around the expensive middle is cheaper than through it
+        let path = grid.astar((1, 0), (1, 2), maze_cost, false).unwrap();
+        assert!(!path.contains(&(1, 1)));
+        let walled = maze(&[
+            ".#.", //
+            "##.", //
+            "...",
+        ]);
+        assert_eq!(walled.astar((0, 0), (2, 2), maze_cost, true), None);
+        assert_eq!(walled.astar((0, 0), (5, 5), maze_cost, false), None);
+    }
+    #[test]
+    fn test_dijkstra_map() {
+        let grid = maze(&[
+            ".....", //
+            ".###.", //
+            "...#.", //
+            "##.#.", //
+            ".....",
+        ]);
+        let open = |_: Coord, c: &char| *c != '#';
+        let dists = grid.dijkstra_map(&[(0, 4)], open);
+        assert_eq!(dists[(0, 4)], Some(0));
+        assert_eq!(dists[(0, 0)], Some(8));
+        assert_eq!(dists[(1, 1)], None);
+        // following the slope from anywhere ends up at the source in
+        // exactly as many steps as the map says
+        let mut here = (4, 0);
+        let mut steps = 0;
+        while let Some(next) = dists.downhill(here) {
+            assert_eq!(dists[next].unwrap() + 1, dists[here].unwrap());
+            here = next;
+            steps += 1;
+        }
+        assert_eq!(here, (0, 4));
+        assert_eq!(Some(steps), dists[(4, 0)]);
+        assert_eq!(dists.uphill((0, 4)), Some((1, 4)));
+        assert_eq!(dists.uphill((4, 0)), Some((3, 0)));
+        assert_eq!(dists.uphill((2, 0)), None);
+        // several sources: each cell counts from its nearest one
+        let dists = grid.dijkstra_map(&[(0, 4), (0, 0)], open);
+        assert_eq!(dists[(2, 2)], Some(4));
+        assert_eq!(dists[(4, 2)], Some(6));
+    }
     #[test]
     fn test_neighbors() {
         let grid = Grid::new(64, 32, vec![0; 64 * 32]);
@@ -0,0 +1,125 @@
+use frenderer::input::{Input, Key};
+
+const LETTERS: [Key; 26] = [
+    Key::KeyA,
+    Key::KeyB,
+    Key::KeyC,
+    Key::KeyD,
+    Key::KeyE,
+    Key::KeyF,
+    Key::KeyG,
+    Key::KeyH,
+    Key::KeyI,
+    Key::KeyJ,
+    Key::KeyK,
+    Key::KeyL,
+    Key::KeyM,
+    Key::KeyN,
+    Key::KeyO,
+    Key::KeyP,
+    Key::KeyQ,
+    Key::KeyR,
+    Key::KeyS,
+    Key::KeyT,
+    Key::KeyU,
+    Key::KeyV,
+    Key::KeyW,
+    Key::KeyX,
+    Key::KeyY,
+    Key::KeyZ,
+];
+
+/// Arcade-style entry of three initials.  Typing a letter fills the
+/// current slot and moves on; up and down cycle the current slot's letter,
+/// left/right and backspace move between slots, and enter confirms.
+#[derive(Clone, Debug)]
+pub struct InitialsEntry {
+    letters: [u8; 3],
+    cursor: usize,
+}
+
+impl Default for InitialsEntry {
+    fn default() -> Self {
+        Self {
+            letters: [b'A'; 3],
+            cursor: 0,
+        }
+    }
+}
+
+impl InitialsEntry {
+    /// Handles the keys pressed since the last frame; true once the player
+    /// has confirmed their initials.
+    pub fn update(&mut self, input: &Input) -> bool {
+        let keys = LETTERS.iter().chain(&[
+            Key::ArrowUp,
+            Key::ArrowDown,
+            Key::ArrowLeft,
+            Key::ArrowRight,
+            Key::Backspace,
+            Key::Enter,
+        ]);
+        let mut done = false;
+        for &key in keys {
+            if input.is_key_pressed(key) {
+                done |= self.press(key);
+            }
+        }
+        done
+    }
+
+    fn press(&mut self, key: Key) -> bool {
+        let last = self.letters.len() - 1;
+        let letter = &mut self.letters[self.cursor];
+        match key {
+            Key::ArrowUp => *letter = (*letter - b'A' + 1) % 26 + b'A',
+            Key::ArrowDown => *letter = (*letter - b'A' + 25) % 26 + b'A',
+            Key::ArrowLeft | Key::Backspace => self.cursor = self.cursor.saturating_sub(1),
+            Key::ArrowRight => self.cursor = (self.cursor + 1).min(last),
+            Key::Enter => return true,
+            _ => {
+                if let Some(idx) = LETTERS.iter().position(|&k| k == key) {
+                    *letter = b'A' + idx as u8;
+                    self.cursor = (self.cursor + 1).min(last);
+                }
+            }
+        }
+        false
+    }
+
+    pub fn initials(&self) -> String {
+        String::from_utf8_lossy(&self.letters).into_owned()
+    }
+
+    /// Which slot typing or cycling changes.
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_entry() {
+        let mut entry = InitialsEntry::default();
+        assert_eq!(entry.initials(), "AAA");
+        for key in [Key::KeyN, Key::KeyV, Key::KeyQ, Key::KeyZ] {
+            assert!(!entry.press(key));
+        }
+        assert_eq!(entry.initials(), "NVZ");
+        assert_eq!(entry.cursor(), 2);
+        entry.press(Key::Backspace);
+        entry.press(Key::ArrowUp);
+        entry.press(Key::ArrowUp);
+        assert_eq!(entry.initials(), "NXZ");
+        entry.press(Key::ArrowLeft);
+        entry.press(Key::ArrowLeft);
+        entry.press(Key::ArrowUp);
+        entry.press(Key::ArrowDown);
+        entry.press(Key::ArrowDown);
+        assert_eq!(entry.initials(), "MXZ");
+        assert_eq!(entry.cursor(), 0);
+        assert!(entry.press(Key::Enter));
+    }
+}
@@ -0,0 +1,370 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// One finished run on the leaderboard.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Entry {
+    pub initials: String,
+    pub score: u32,
+    /// The level the run started in.
+    pub level: String,
+    /// `None` for runs saved before seeds were recorded.
+    pub seed: Option<u64>,
+    /// Seconds of play.
+    pub duration: f32,
+    /// Seconds since the Unix epoch when the run ended, if known.
+    pub timestamp: Option<u64>,
+}
+
+/// Every saved score, best first.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct Leaderboard {
+    entries: Vec<Entry>,
+}
+
+/*
+Version 2 leaderboards are text files with one tab-separated entry per
+line, `-` standing in for unknown values:
+
+leaderboard 2
+INITIALS	SCORE	LEVEL	SEED	DURATION	TIMESTAMP
+
+Anything without the header is a version 1 file of `INITIALS,SCORE` or
+`INITIALS,SCORE,SEED` lines.  Those runs all started in level1 and only
+full 60 second wins were saved, so that's what they migrate to.
+*/
+const HEADER: &str = "leaderboard 2";
+
+// A lock older than this was left behind by a session that crashed while
+// holding it; holding one normally takes a few milliseconds.
+const STALE_LOCK_AGE: Duration = Duration::from_secs(10);
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |t| t.as_secs())
+}
+
+// Held while a session reads, changes and writes the leaderboard; the
+// file is removed again when this is dropped.  It holds the holder's pid
+// and when it was taken, as `PID SECS`.
+struct Lock(PathBuf);
+
+impl Lock {
+    // One go at taking the lock, breaking it first if it's stale.  `None`
+    // means another session has it.
+    fn try_acquire(path: &Path) -> io::Result<Option<Self>> {
+        let mut lock_name = path.file_name().unwrap_or_default().to_os_string();
+        lock_name.push(".lock");
+        let lock_path = path.with_file_name(lock_name);
+        for _ in 0..2 {
+            match fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&lock_path)
+            {
+                Ok(mut file) => {
+                    let lock = Self(lock_path);
+                    writeln!(file, "{} {}", std::process::id(), now_secs())?;
+                    return Ok(Some(lock));
+                }
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                    if !Self::is_stale(&lock_path) {
+                        return Ok(None);
+                    }
+                    eprintln!("Breaking stale lock {}", lock_path.display());
+                    match fs::remove_file(&lock_path) {
+                        Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                        _ => {}
+                    }
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        // someone else took it straight after it was broken
+        Ok(None)
+    }
+
+    fn is_stale(lock_path: &Path) -> bool {
+        let taken = fs::read_to_string(lock_path)
+            .ok()
+            .and_then(|text| text.split_whitespace().nth(1)?.parse::<u64>().ok());
+        let age = match taken {
+            Some(secs) => Duration::from_secs(now_secs().saturating_sub(secs)),
+            // still being written, or written by something else
+            None => match fs::metadata(lock_path).and_then(|meta| meta.modified()) {
+                Ok(modified) => modified.elapsed().unwrap_or_default(),
+                Err(_) => return false,
+            },
+        };
+        age > STALE_LOCK_AGE
+    }
+}
+
+impl Drop for Lock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+fn bad_line(line: usize) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("malformed leaderboard entry on line {line}"),
+    )
+}
+
+fn parse_optional<T: std::str::FromStr>(field: &str) -> Option<Option<T>> {
+    if field == "-" {
+        Some(None)
+    } else {
+        field.parse().ok().map(Some)
+    }
+}
+
+fn optional_to_string<T: ToString>(value: Option<T>) -> String {
+    value.map_or("-".to_string(), |v| v.to_string())
+}
+
+impl Leaderboard {
+    /// Reads a leaderboard in either format; a missing file is an empty
+    /// leaderboard.  Malformed entries are an error rather than being
+    /// dropped, so saving can't throw away scores.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        match fs::read_to_string(path) {
+            Ok(text) => Self::parse(&text),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn parse(text: &str) -> io::Result<Self> {
+        let mut lines = text.lines().enumerate().map(|(idx, line)| (idx + 1, line));
+        let mut leaderboard = Self::default();
+        let version_2 = text.lines().next() == Some(HEADER);
+        if version_2 {
+            lines.next();
+        }
+        for (line_no, line) in lines {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let entry = if version_2 {
+                Self::parse_entry(line)
+            } else {
+                Self::parse_csv_entry(line)
+            };
+            leaderboard.add(entry.ok_or_else(|| bad_line(line_no))?);
+        }
+        Ok(leaderboard)
+    }
+
+    fn parse_entry(line: &str) -> Option<Entry> {
+        let fields: Vec<&str> = line.split('\t').collect();
+        let [initials, score, level, seed, duration, timestamp] = fields[..] else {
+            return None;
+        };
+        Some(Entry {
+            initials: initials.to_string(),
+            score: score.parse().ok()?,
+            level: level.to_string(),
+            seed: parse_optional(seed)?,
+            duration: duration.parse().ok()?,
+            timestamp: parse_optional(timestamp)?,
+        })
+    }
+
+    fn parse_csv_entry(line: &str) -> Option<Entry> {
+        let parts: Vec<&str> = line.split(',').collect();
+        let (initials, score, seed) = match parts[..] {
+            [initials, score] => (initials, score, None),
+            [initials, score, seed] => (initials, score, Some(seed.parse().ok()?)),
+            _ => return None,
+        };
+        Some(Entry {
+            initials: initials.to_string(),
+            score: score.parse().ok()?,
+            level: "level1".to_string(),
+            seed,
+            duration: 60.0,
+            timestamp: None,
+        })
+    }
+
+    /// Writes the whole leaderboard to a temporary file next to `path` and
+    /// renames it into place, so readers never see a half-written file.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut text = format!("{HEADER}\n");
+        for entry in &self.entries {
+            text += &format!(
+                "{}\t{}\t{}\t{}\t{}\t{}\n",
+                entry.initials,
+                entry.score,
+                entry.level,
+                optional_to_string(entry.seed),
+                entry.duration,
+                optional_to_string(entry.timestamp)
+            );
+        }
+        let mut tmp_name = path.file_name().unwrap_or_default().to_os_string();
+        tmp_name.push(format!(".{}.tmp", std::process::id()));
+        let tmp_path = path.with_file_name(tmp_name);
+        fs::write(&tmp_path, text)?;
+        fs::rename(&tmp_path, path).inspect_err(|_| {
+            let _ = fs::remove_file(&tmp_path);
+        })
+    }
+
+    /// Loads the leaderboard at `path`, adds `entry` and saves it again,
+    /// returning the updated leaderboard.  A `.lock` file next to `path`
+    /// is held throughout, so sessions recording at the same time through
+    /// this function don't lose each other's scores.  This never waits:
+    /// if another session holds the lock it records nothing and returns
+    /// `None`, and the caller should try again later.  A lock left behind
+    /// by a crashed session is broken once it's ten seconds old.
+    pub fn try_record(path: &Path, entry: &Entry) -> io::Result<Option<Self>> {
+        let Some(_lock) = Lock::try_acquire(path)? else {
+            return Ok(None);
+        };
+        let mut leaderboard = Self::load(path)?;
+        leaderboard.add(entry.clone());
+        leaderboard.save(path)?;
+        Ok(Some(leaderboard))
+    }
+
+    pub fn add(&mut self, mut entry: Entry) {
+        // tabs and newlines would break the file format
+        entry.initials = entry
+            .initials
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ");
+        // after any equal scores, so earlier runs keep their place
+        let at = self.entries.partition_point(|e| e.score >= entry.score);
+        self.entries.insert(at, entry);
+    }
+
+    /// The `n` best runs on any level.
+    pub fn top(&self, n: usize) -> impl Iterator<Item = &Entry> {
+        self.entries.iter().take(n)
+    }
+
+    /// The `n` best runs that started in `level`.
+    pub fn top_for_level<'a>(
+        &'a self,
+        level: &'a str,
+        n: usize,
+    ) -> impl Iterator<Item = &'a Entry> {
+        self.entries
+            .iter()
+            .filter(move |e| e.level == level)
+            .take(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    fn entry(initials: &str, score: u32, level: &str) -> Entry {
+        Entry {
+            initials: initials.to_string(),
+            score,
+            level: level.to_string(),
+            seed: Some(score as u64 * 7),
+            duration: 60.0,
+            timestamp: Some(1_700_000_000),
+        }
+    }
+    #[test]
+    fn test_migrate_csv() {
+        let board = Leaderboard::parse("NV,64\nAB,6,99\n\nNV,88\n").unwrap();
+        let entries: Vec<&Entry> = board.top(10).collect();
+        let scores: Vec<u32> = entries.iter().map(|e| e.score).collect();
+        assert_eq!(scores, vec![88, 64, 6]);
+        assert_eq!(entries[2].seed, Some(99));
+        assert_eq!(entries[0].seed, None);
+        assert!(entries.iter().all(|e| e.level == "level1"));
+        assert!(Leaderboard::parse("NV,64\nNV\n").is_err());
+        assert!(Leaderboard::parse("NV,lots\n").is_err());
+    }
+    #[test]
+    fn test_queries() {
+        let mut board = Leaderboard::default();
+        board.add(entry("A", 5, "level1"));
+        board.add(entry("B", 9, "level2"));
+        board.add(entry("C", 7, "level1"));
+        board.add(entry("D", 7, "level2"));
+        let initials = |entries: Vec<&Entry>| {
+            entries
+                .iter()
+                .map(|e| e.initials.as_str())
+                .collect::<String>()
+        };
+        assert_eq!(initials(board.top(3).collect()), "BCD");
+        assert_eq!(board.top(10).count(), 4);
+        assert_eq!(initials(board.top_for_level("level1", 5).collect()), "CA");
+        assert_eq!(initials(board.top_for_level("level2", 1).collect()), "B");
+        assert_eq!(board.top_for_level("level9", 5).count(), 0);
+    }
+    #[test]
+    fn test_save_and_load() {
+        let path = std::env::temp_dir().join(format!("leaderboard-test-{}", std::process::id()));
+        let _ = fs::remove_file(&path);
+        assert_eq!(Leaderboard::load(&path).unwrap(), Leaderboard::default());
+        fs::write(&path, "NV,64\n").unwrap();
+        let mut odd = entry("X\tY", 70, "level2");
+        odd.seed = None;
+        odd.timestamp = None;
+        odd.duration = 12.5;
+        let board = Leaderboard::try_record(&path, &odd).unwrap().unwrap();
+        let text = fs::read_to_string(&path).unwrap();
+        assert!(text.starts_with("leaderboard 2\nX Y\t70\tlevel2\t-\t12.5\t-\n"));
+        assert_eq!(Leaderboard::load(&path).unwrap(), board);
+        fs::remove_file(&path).unwrap();
+    }
+    #[test]
+    fn test_concurrent_records() {
+        let path = std::env::temp_dir().join(format!("leaderboard-race-{}", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let sessions: Vec<_> = (0..4)
+            .map(|session| {
+                let path = path.clone();
+                std::thread::spawn(move || {
+                    for score in 0..10 {
+                        let entry = entry("AB", session * 10 + score, "level1");
+                        while Leaderboard::try_record(&path, &entry).unwrap().is_none() {
+                            std::thread::yield_now();
+                        }
+                    }
+                })
+            })
+            .collect();
+        for session in sessions {
+            session.join().unwrap();
+        }
+        assert_eq!(Leaderboard::load(&path).unwrap().top(100).count(), 40);
+        fs::remove_file(&path).unwrap();
+    }
+    #[test]
+    fn test_stale_lock() {
+        let path = std::env::temp_dir().join(format!("leaderboard-stale-{}", std::process::id()));
+        let lock_path = path.with_file_name(format!(
+            "{}.lock",
+            path.file_name().unwrap().to_string_lossy()
+        ));
+        let _ = fs::remove_file(&path);
+        // a live session's lock is left alone
+        fs::write(&lock_path, format!("1 {}\n", now_secs())).unwrap();
+        let score = entry("AB", 5, "level1");
+        assert_eq!(Leaderboard::try_record(&path, &score).unwrap(), None);
+        assert!(!path.exists());
+        // one from a session that died a minute ago isn't
+        fs::write(&lock_path, format!("1 {}\n", now_secs() - 60)).unwrap();
+        let board = Leaderboard::try_record(&path, &score).unwrap().unwrap();
+        assert_eq!(board.top(10).count(), 1);
+        assert!(!lock_path.exists());
+        fs::remove_file(&path).unwrap();
+    }
+}
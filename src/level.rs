@@ -2,8 +2,12 @@ use crate::geom::*;
 use crate::grid::Coord;
 use crate::grid::Grid;
 use crate::EntityType;
+use crate::TileAnimation;
 use crate::TileData;
+use crate::TileFlags;
+use crate::TileOrientation;
 use crate::TILE_SZ;
+use assets_manager::AssetCache;
 use frenderer::{
     sprites::{SheetRegion, Transform},
     Renderer,
@@ -14,6 +18,8 @@ use std::str::FromStr;
 #[derive(Debug)]
 struct Tileset {
     tiles: Vec<TileData>,
+    // the legend symbol for each tile, kept so the level can be written back out
+    symbols: Vec<String>,
 }
 impl std::ops::Index<usize> for Tileset {
     type Output = TileData;
@@ -22,21 +28,407 @@ impl std::ops::Index<usize> for Tileset {
     }
 }
 
+/// One named map of tile indices.  Layers are drawn in order, each over
+/// the ones before it.
+pub struct Layer {
+    name: String,
+    // indices into the tileset, or NO_TILE where nothing is drawn
+    grid: Grid<u8>,
+}
+
+impl Layer {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    pub fn grid(&self) -> &Grid<u8> {
+        &self.grid
+    }
+}
+
 #[allow(dead_code)]
 pub struct Level {
     name: String,
-    grid: Grid<u8>,
+    layers: Vec<Layer>,
+    // which layer's tiles things collide with
+    collision: usize,
     tileset: Tileset,
     starts: Vec<(EntityType, Vec2)>,
+    // fixed random seed for runs that start here, if the designer set one
+    seed: Option<u64>,
 }
 
 const WALL: SheetRegion = SheetRegion::new(0, 186, 186, 0, 18, 18);
 
-impl Level {
+// The map symbol for a cell with no tile, unless the legend defines it.
+const EMPTY_SYMBOL: &str = "_";
+const NO_TILE: u8 = u8::MAX;
+// The name of the layer in files that don't name their map.
+const DEFAULT_LAYER: &str = "map";
+
+// The legend letter for each tile flag, in the order they're written out.
+// `o` (open) is also accepted and means no flags.
+const FLAG_LETTERS: [(char, TileFlags); 6] = [
+    ('s', TileFlags::SOLID),
+    ('d', TileFlags::DAMAGING),
+    ('m', TileFlags::SLOW),
+    ('v', TileFlags::ONE_WAY),
+    ('w', TileFlags::WATER),
+    ('g', TileFlags::GOAL),
+];
+
+// Flags from legend letters like `md`, or None if any letter is unknown.
+fn parse_flags(letters: &str) -> Option<TileFlags> {
+    letters.chars().try_fold(TileFlags::empty(), |flags, c| {
+        let c = c.to_ascii_lowercase();
+        if c == 'o' {
+            return Some(flags);
+        }
+        let (_, flag) = FLAG_LETTERS.iter().find(|(letter, _)| *letter == c)?;
+        Some(flags | *flag)
+    })
+}
+
+// The legend suffixes that flip or rotate a tile, and what each one sets.
+fn parse_orientation_suffix(suffix: &str, orientation: &mut TileOrientation) -> Option<()> {
+    match suffix {
+        "fx" => orientation.flip_x = true,
+        "fy" => orientation.flip_y = true,
+        "r90" => orientation.quarter_turns = 1,
+        "r180" => orientation.quarter_turns = 2,
+        "r270" => orientation.quarter_turns = 3,
+        _ => return None,
+    }
+    Some(())
+}
+
+fn write_orientation(orientation: TileOrientation) -> String {
+    let mut suffixes = String::new();
+    if orientation.flip_x {
+        suffixes.push_str(" fx");
+    }
+    if orientation.flip_y {
+        suffixes.push_str(" fy");
+    }
+    if orientation.quarter_turns > 0 {
+        suffixes.push_str(&format!(" r{}", orientation.quarter_turns as u32 * 90));
+    }
+    suffixes
+}
+
+fn write_animation(animation: &TileAnimation) -> String {
+    let mut suffix = format!(" anim {}", animation.frame_time);
+    for (x, y) in &animation.frames[1..] {
+        suffix.push_str(&format!(" {x} {y}"));
+    }
+    suffix
+}
+
+fn write_flags(flags: TileFlags) -> String {
+    if flags.is_empty() {
+        return "o".to_string();
+    }
+    FLAG_LETTERS
+        .iter()
+        .filter(|(_, flag)| flags.contains(*flag))
+        .map(|(letter, _)| letter)
+        .collect()
+}
+
+/// Everything that can go wrong while reading a level file.  Lines and
+/// columns are 1-based so they match what a text editor shows.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LevelParseError {
+    /// The `LEVELNAME W H` line is absent.
+    MissingHeader { line: usize, col: usize },
+    /// The width or height in the header is missing or not a u16.
+    BadDims { line: usize, col: usize },
+    /// The header's `seed=N` is not a u64.
+    BadSeed { line: usize, col: usize },
+    /// No tiles were declared before the map section.
+    MissingLegend { line: usize, col: usize },
+    /// A legend symbol was declared twice.
+    DuplicateLegendSymbol {
+        line: usize,
+        col: usize,
+        sym: String,
+    },
+    /// The legend has more tiles than a map cell can name.
+    TooManyTiles { line: usize, col: usize },
+    /// A legend line has missing or invalid flags, sheet coordinates or
+    /// suffixes.
+    BadLegendEntry { line: usize, col: usize },
+    /// A `layer` line is missing its name or has something other than
+    /// `collision` after it, or a second layer is marked for collision.
+    BadLayer { line: usize, col: usize },
+    /// Two map layers have the same name.
+    DuplicateLayer {
+        line: usize,
+        col: usize,
+        name: String,
+    },
+    /// The map uses a symbol that is not in the legend.
+    UnknownMapSymbol {
+        line: usize,
+        col: usize,
+        sym: String,
+    },
+    ShortMapRow {
+        line: usize,
+        col: usize,
+        expected: usize,
+        found: usize,
+    },
+    LongMapRow {
+        line: usize,
+        col: usize,
+        expected: usize,
+        found: usize,
+    },
+    /// The map has a different number of rows than the header says.
+    WrongRowCount {
+        line: usize,
+        col: usize,
+        expected: usize,
+        found: usize,
+    },
+    UnknownEntityType {
+        line: usize,
+        col: usize,
+        etype: String,
+    },
+    /// A start line has missing or invalid coordinates.
+    BadStart { line: usize, col: usize },
+    /// Anything left over after a line or section should have ended.
+    TrailingContent { line: usize, col: usize },
+}
+
+impl LevelParseError {
+    fn bad_dims(line: usize, col: usize) -> Self {
+        Self::BadDims { line, col }
+    }
+    fn bad_legend(line: usize, col: usize) -> Self {
+        Self::BadLegendEntry { line, col }
+    }
+    fn bad_start(line: usize, col: usize) -> Self {
+        Self::BadStart { line, col }
+    }
+    pub fn line(&self) -> usize {
+        match self {
+            Self::MissingHeader { line, .. }
+            | Self::BadDims { line, .. }
+            | Self::BadSeed { line, .. }
+            | Self::MissingLegend { line, .. }
+            | Self::DuplicateLegendSymbol { line, .. }
+            | Self::TooManyTiles { line, .. }
+            | Self::BadLegendEntry { line, .. }
+            | Self::BadLayer { line, .. }
+            | Self::DuplicateLayer { line, .. }
+            | Self::UnknownMapSymbol { line, .. }
+            | Self::ShortMapRow { line, .. }
+            | Self::LongMapRow { line, .. }
+            | Self::WrongRowCount { line, .. }
+            | Self::UnknownEntityType { line, .. }
+            | Self::BadStart { line, .. }
+            | Self::TrailingContent { line, .. } => *line,
+        }
+    }
+    pub fn col(&self) -> usize {
+        match self {
+            Self::MissingHeader { col, .. }
+            | Self::MissingLegend { col, .. }
+            | Self::WrongRowCount { col, .. }
+            | Self::BadDims { col, .. }
+            | Self::BadSeed { col, .. }
+            | Self::DuplicateLegendSymbol { col, .. }
+            | Self::TooManyTiles { col, .. }
+            | Self::BadLegendEntry { col, .. }
+            | Self::BadLayer { col, .. }
+            | Self::DuplicateLayer { col, .. }
+            | Self::UnknownMapSymbol { col, .. }
+            | Self::ShortMapRow { col, .. }
+            | Self::LongMapRow { col, .. }
+            | Self::UnknownEntityType { col, .. }
+            | Self::BadStart { col, .. }
+            | Self::TrailingContent { col, .. } => *col,
+        }
+    }
+}
+
+impl std::fmt::Display for LevelParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}: ", self.line(), self.col())?;
+        match self {
+            Self::MissingHeader { .. } => write!(f, "expected a `LEVELNAME W H` line first"),
+            Self::BadDims { .. } => write!(f, "level width and height must be numbers 0-65535"),
+            Self::BadSeed { .. } => write!(f, "seed=N needs a whole number 0 or larger"),
+            Self::MissingLegend { .. } => write!(f, "the legend has no tiles in it"),
+            Self::DuplicateLegendSymbol { sym, .. } => {
+                write!(f, "symbol {sym} is already in the legend")
+            }
+            Self::TooManyTiles { .. } => {
+                write!(f, "the legend can't have more than {NO_TILE} tiles")
+            }
+            Self::BadLegendEntry { .. } => write!(
+                f,
+                "legend lines look like `SYM FLAGS [X Y W H] [fx] [fy] [r90|r180|r270] \
+                 [anim SECS X Y...]` where \
+                 FLAGS is o(pen) or any of s(olid), d(amaging), m(ud), v (one-way down), \
+                 w(ater) and g(oal)"
+            ),
+            Self::BadLayer { .. } => write!(
+                f,
+                "layer lines look like `layer NAME [collision]`, with one collision layer at most"
+            ),
+            Self::DuplicateLayer { name, .. } => write!(f, "there is already a layer {name}"),
+            Self::UnknownMapSymbol { sym, .. } => write!(f, "symbol {sym} is not in the legend"),
+            Self::ShortMapRow {
+                expected, found, ..
+            } => write!(
+                f,
+                "map row has {found} tiles but the level is {expected} wide"
+            ),
+            Self::LongMapRow {
+                expected, found, ..
+            } => write!(
+                f,
+                "map row has {found} tiles but the level is {expected} wide"
+            ),
+            Self::WrongRowCount {
+                expected, found, ..
+            } => write!(f, "map has {found} rows but the level is {expected} tall"),
+            Self::UnknownEntityType { etype, .. } => {
+                write!(f, "unrecognized entity type {etype}")
+            }
+            Self::BadStart { .. } => write!(
+                f,
+                "start lines look like `TYPE X Y` or `door LEVELNAME TO-X TO-Y X Y`"
+            ),
+            Self::TrailingContent { .. } => write!(f, "unexpected content"),
+        }
+    }
+}
+
+impl std::error::Error for LevelParseError {}
+
+/// Why a level (or one it leads to) couldn't be loaded by [`load_world`].
+#[derive(Debug)]
+pub enum LevelLoadError {
+    /// There is no `NAME.txt` in the asset cache.
+    Missing(String),
+    Parse(String, LevelParseError),
+    /// The file's header names a different level than the file does.
+    NameMismatch(String, String),
+}
+
+impl std::fmt::Display for LevelLoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Missing(name) => write!(f, "{name}.txt: couldn't load level"),
+            Self::Parse(name, err) => write!(f, "{name}.txt:{err}"),
+            Self::NameMismatch(name, header) => {
+                write!(f, "{name}.txt: header calls the level {header}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for LevelLoadError {}
+
+/// Loads the level `start` and every level reachable from it through
+/// doors, keyed by level name.
+pub fn load_world(
+    cache: &AssetCache,
+    start: &str,
+) -> Result<HashMap<String, Level>, LevelLoadError> {
+    let mut levels = HashMap::new();
+    let mut to_load = vec![start.to_string()];
+    while let Some(name) = to_load.pop() {
+        if levels.contains_key(&name) {
+            continue;
+        }
+        let handle = cache
+            .load::<String>(&name)
+            .map_err(|_| LevelLoadError::Missing(name.clone()))?;
+        let level =
+            Level::from_str(&handle.read()).map_err(|e| LevelLoadError::Parse(name.clone(), e))?;
+        if level.name() != name {
+            return Err(LevelLoadError::NameMismatch(name, level.name.clone()));
+        }
+        for (etype, _pos) in level.starts() {
+            if let EntityType::Door(to_room, _, _) = etype {
+                to_load.push(to_room.clone());
+            }
+        }
+        levels.insert(name, level);
+    }
+    Ok(levels)
+}
+
+/// Whitespace-separated words of a line along with their 1-based column.
+#[derive(Clone)]
+struct Tokens<'a> {
+    line: &'a str,
+    line_no: usize,
+    rest: std::iter::Peekable<std::str::CharIndices<'a>>,
+}
+
+impl<'a> Tokens<'a> {
+    fn new(line: &'a str, line_no: usize) -> Self {
+        Self {
+            line,
+            line_no,
+            rest: line.char_indices().peekable(),
+        }
+    }
+    /// Column of the next token, or one past the end of the line.
+    fn col(&mut self) -> usize {
+        while self.rest.next_if(|(_, c)| c.is_whitespace()).is_some() {}
+        let byte = self.rest.peek().map_or(self.line.len(), |(b, _)| *b);
+        self.line[..byte].chars().count() + 1
+    }
+    /// The next token, without moving past it.
+    fn peek(&self) -> Option<(usize, &'a str)> {
+        self.clone().next()
+    }
+    fn parse<T: FromStr>(
+        &mut self,
+        err: fn(usize, usize) -> LevelParseError,
+    ) -> Result<T, LevelParseError> {
+        let col = self.col();
+        self.next()
+            .and_then(|(_, tok)| tok.parse().ok())
+            .ok_or_else(|| err(self.line_no, col))
+    }
+}
+
+impl<'a> Iterator for Tokens<'a> {
+    type Item = (usize, &'a str);
+    fn next(&mut self) -> Option<Self::Item> {
+        let col = self.col();
+        let (start, _) = *self.rest.peek()?;
+        let mut end = self.line.len();
+        for (b, c) in self.rest.by_ref() {
+            if c.is_whitespace() {
+                end = b;
+                break;
+            }
+        }
+        Some((col, &self.line[start..end]))
+    }
+}
+
+impl FromStr for Level {
+    type Err = LevelParseError;
     /*
-    We'll read from an ad hoc format like this, where FLAGS is either S (solid) or O (open) but could be other stuff later:
+    We'll read from an ad hoc format like this, where FLAGS is O (open) or
+    some of S (solid), D (damaging), M (mud, slow), V (one-way, enter moving
+    down only), W (water, blocks enemies) and G (goal), like `md`.  The
+    sheet coordinates can be left out to draw a plain wall, and the line can
+    end with FX and FY to flip the tile and R90, R180 or R270 to turn it
+    clockwise.  `ANIM SECS X Y X Y...` makes the tile cycle between its own
+    region and ones the same size at each X Y, showing each for SECS:
 
-    LEVELNAME W H
+    LEVELNAME W H [seed=N]
     ====
     SYM FLAGS X Y W H
     SYM FLAGS X Y W H
@@ -48,6 +440,20 @@ impl Level {
     SYM SYM SYM SYM SYM
     SYM SYM SYM SYM SYM
     ====
+
+    The map can also be split into layers drawn one over the other, each
+    starting with a `layer NAME` line.  Things collide with the first layer,
+    or the one whose line ends in `collision`.  `_` leaves a cell of a layer
+    empty, unless the legend uses it as a symbol:
+
+    ====
+    layer floor
+    SYM SYM SYM SYM SYM
+    ...
+    layer decorations
+    _ SYM _ _ SYM
+    ...
+    ====
     player X Y
     enemy X Y
     enemy X Y
@@ -55,7 +461,7 @@ impl Level {
     door LEVELNAME TO-X TO-Y X Y
     you can add more types of thing if you want
     */
-    pub fn from_str(s: &str) -> Self {
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
         enum State {
             Metadata,
             Legend,
@@ -76,187 +482,836 @@ impl Level {
         }
         let mut state = State::Metadata;
         let mut name = None;
-        let mut dims = None;
+        let mut dims: Option<(u16, u16)> = None;
+        let mut seed = None;
         let mut legend: HashMap<String, (u8, TileData)> = std::collections::HashMap::new();
-        let mut grid = vec![];
+        // each layer's name and tiles so far
+        let mut layers: Vec<(String, Vec<u8>)> = vec![];
+        let mut collision = None;
+        // rows in the current layer
+        let mut rows = 0;
         let mut starts = vec![];
-        for line in s.lines() {
-            if line.is_empty() {
+        let mut last_line = 0;
+        for (line_idx, line) in s.lines().enumerate() {
+            let line_no = line_idx + 1;
+            last_line = line_no;
+            if line.trim().is_empty() {
                 continue;
             } else if line.chars().all(|c| c == '=') {
+                // the separator is all `=`, so it starts in the first column
+                let col = 1;
                 state = state.next();
-            } else {
                 match state {
-                    State::Metadata => {
-                        assert_eq!(name, None, "Two metadata lines! in {line}");
-                        let mut chunks = line.split_whitespace();
-                        name = Some(
-                            chunks
-                                .next()
-                                .expect("No name in metadata line {line}")
-                                .to_string(),
+                    State::Legend if dims.is_none() => {
+                        return Err(LevelParseError::MissingHeader { line: line_no, col });
+                    }
+                    State::Map if legend.is_empty() => {
+                        return Err(LevelParseError::MissingLegend { line: line_no, col });
+                    }
+                    State::Starts if rows != dims.unwrap().1 as usize => {
+                        return Err(LevelParseError::WrongRowCount {
+                            line: line_no,
+                            col,
+                            expected: dims.unwrap().1 as usize,
+                            found: rows,
+                        });
+                    }
+                    _ => {}
+                }
+                continue;
+            }
+            let mut chunks = Tokens::new(line, line_no);
+            match state {
+                State::Metadata => {
+                    if name.is_some() {
+                        return Err(LevelParseError::TrailingContent {
+                            line: line_no,
+                            col: chunks.col(),
+                        });
+                    }
+                    let (_, level_name) = chunks.next().unwrap();
+                    name = Some(level_name.to_string());
+                    let w: u16 = chunks.parse(LevelParseError::bad_dims)?;
+                    let h: u16 = chunks.parse(LevelParseError::bad_dims)?;
+                    dims = Some((w, h));
+                    if let Some((col, tok)) = chunks.next() {
+                        let Some(n) = tok.strip_prefix("seed=") else {
+                            return Err(LevelParseError::TrailingContent { line: line_no, col });
+                        };
+                        seed = Some(
+                            u64::from_str(n)
+                                .map_err(|_| LevelParseError::BadSeed { line: line_no, col })?,
                         );
-                        dims = Some((
-                            u16::from_str(chunks.next().expect("No width in metadata line {line}"))
-                                .expect("Couldn't parse width as u16 in {line}"),
-                            u16::from_str(
-                                chunks.next().expect("No height in metadata line {line}"),
-                            )
-                            .expect("Couldn't parse height as u16 in {line}"),
-                        ));
                     }
-                    State::Legend => {
-                        let mut chunks = line.split_whitespace();
-                        let sym = chunks.next().expect("Couldn't get tile symbol in {line}");
-                        assert!(!legend.contains_key(sym), "Symbol {sym} already in legend");
-                        let flags = chunks
-                            .next()
-                            .expect("Couldn't get tile flags in {line}")
-                            .to_lowercase();
-                        assert!(flags == "o" || flags == "s", "The only valid flags are o(pen) or s(olid), but you could parse other kinds here in {line}");
-                        let x =
-                            u16::from_str(chunks.next().expect("No sheet x in legend line {line}"))
-                                .expect("Couldn't parse sheet x as u16 in {line}");
-                        let y =
-                            u16::from_str(chunks.next().expect("No sheet y in legend line {line}"))
-                                .expect("Couldn't parse sheet y as u16 in {line}");
-                        let w =
-                            i16::from_str(chunks.next().expect("No sheet w in legend line {line}"))
-                                .expect("Couldn't parse sheet w as i16 in {line}");
-                        let h =
-                            i16::from_str(chunks.next().expect("No sheet h in legend line {line}"))
-                                .expect("Couldn't parse sheet h as i16 in {line}");
-                        let data = TileData {
-                            solid: flags == "s",
-                            sheet_region: SheetRegion::new(0, x, y, 16, w, h),
+                }
+                State::Legend => {
+                    let (col, sym) = chunks.next().unwrap();
+                    if legend.contains_key(sym) {
+                        return Err(LevelParseError::DuplicateLegendSymbol {
+                            line: line_no,
+                            col,
+                            sym: sym.to_string(),
+                        });
+                    }
+                    // NO_TILE is the last index a u8 can hold
+                    if legend.len() == NO_TILE as usize {
+                        return Err(LevelParseError::TooManyTiles { line: line_no, col });
+                    }
+                    let (col, flags) = chunks
+                        .next()
+                        .ok_or_else(|| LevelParseError::bad_legend(line_no, chunks.col()))?;
+                    let flags = parse_flags(flags)
+                        .ok_or_else(|| LevelParseError::bad_legend(line_no, col))?;
+                    let starts_number = |tok: Option<(usize, &str)>| {
+                        tok.is_some_and(|(_, tok)| tok.starts_with(|c: char| c.is_ascii_digit()))
+                    };
+                    let sheet_region = match chunks.peek() {
+                        tok if starts_number(tok) => {
+                            let x = chunks.parse(LevelParseError::bad_legend)?;
+                            let y = chunks.parse(LevelParseError::bad_legend)?;
+                            let w = chunks.parse(LevelParseError::bad_legend)?;
+                            let h = chunks.parse(LevelParseError::bad_legend)?;
+                            Some(SheetRegion::new(0, x, y, 16, w, h))
+                        }
+                        _ => None,
+                    };
+                    let mut orientation = TileOrientation::default();
+                    let mut animation = None;
+                    while let Some((col, suffix)) = chunks.next() {
+                        let suffix = suffix.to_lowercase();
+                        if suffix != "anim" {
+                            parse_orientation_suffix(&suffix, &mut orientation)
+                                .ok_or_else(|| LevelParseError::bad_legend(line_no, col))?;
+                            continue;
+                        }
+                        // the first frame is the tile's own region
+                        let Some(region) = sheet_region else {
+                            return Err(LevelParseError::bad_legend(line_no, col));
                         };
-                        legend.insert(sym.to_string(), (legend.len() as u8, data));
+                        let time_col = chunks.col();
+                        let frame_time: f32 = chunks.parse(LevelParseError::bad_legend)?;
+                        if frame_time <= 0.0 || !frame_time.is_finite() {
+                            return Err(LevelParseError::bad_legend(line_no, time_col));
+                        }
+                        let mut frames = vec![(region.x, region.y)];
+                        while starts_number(chunks.peek()) {
+                            let x = chunks.parse(LevelParseError::bad_legend)?;
+                            let y = chunks.parse(LevelParseError::bad_legend)?;
+                            frames.push((x, y));
+                        }
+                        if frames.len() < 2 {
+                            return Err(LevelParseError::bad_legend(line_no, chunks.col()));
+                        }
+                        animation = Some(TileAnimation { frame_time, frames });
                     }
-                    State::Map => {
-                        let old_len = grid.len();
-                        grid.extend(line.split_whitespace().map(|sym| legend[sym].0));
-                        assert_eq!(
-                            old_len + dims.unwrap().0 as usize,
-                            grid.len(),
-                            "map line is too short: {line} for map dims {dims:?}"
-                        );
+                    let data = TileData {
+                        flags,
+                        sheet_region,
+                        orientation,
+                        animation,
+                    };
+                    legend.insert(sym.to_string(), (legend.len() as u8, data));
+                }
+                State::Map if matches!(chunks.peek(), Some((_, "layer"))) => {
+                    let (layer_col, _) = chunks.next().unwrap();
+                    let h = dims.unwrap().1 as usize;
+                    if !layers.is_empty() && rows != h {
+                        return Err(LevelParseError::WrongRowCount {
+                            line: line_no,
+                            col: layer_col,
+                            expected: h,
+                            found: rows,
+                        });
                     }
-                    State::Starts => {
-                        let mut chunks = line.split_whitespace();
-                        let etype = chunks
-                            .next()
-                            .expect("Couldn't get entity start type {line}");
-                        let etype = match etype {
-                            "player" => EntityType::Player,
-                            "enemy" => EntityType::Enemy,
-                            "door" => {
-                                let to_room = chunks.next().expect("Couldn't get dest room {line}");
-                                let to_x = u16::from_str(
-                                    chunks.next().expect("No dest x coord in door line {line}"),
-                                )
-                                .expect("Couldn't parse x coord as u16 in {line}");
-                                let to_y = u16::from_str(
-                                    chunks.next().expect("No dest y coord in door line {line}"),
-                                )
-                                .expect("Couldn't parse y coord as u16 in {line}");
-                                EntityType::Door(to_room.to_string(), to_x, to_y)
+                    let (col, layer_name) =
+                        chunks.next().ok_or_else(|| LevelParseError::BadLayer {
+                            line: line_no,
+                            col: chunks.col(),
+                        })?;
+                    if layers.iter().any(|(name, _)| name == layer_name) {
+                        return Err(LevelParseError::DuplicateLayer {
+                            line: line_no,
+                            col,
+                            name: layer_name.to_string(),
+                        });
+                    }
+                    if let Some((col, tok)) = chunks.next() {
+                        if tok != "collision" || collision.is_some() {
+                            return Err(LevelParseError::BadLayer { line: line_no, col });
+                        }
+                        collision = Some(layers.len());
+                    }
+                    layers.push((layer_name.to_string(), vec![]));
+                    rows = 0;
+                }
+                State::Map => {
+                    let (w, h) = dims.unwrap();
+                    if layers.is_empty() {
+                        layers.push((DEFAULT_LAYER.to_string(), vec![]));
+                    }
+                    if rows == h as usize {
+                        return Err(LevelParseError::WrongRowCount {
+                            line: line_no,
+                            col: chunks.col(),
+                            expected: h as usize,
+                            found: rows + 1,
+                        });
+                    }
+                    let syms: Vec<_> = chunks.by_ref().collect();
+                    if syms.len() > w as usize {
+                        return Err(LevelParseError::LongMapRow {
+                            line: line_no,
+                            col: syms[w as usize].0,
+                            expected: w as usize,
+                            found: syms.len(),
+                        });
+                    } else if syms.len() < w as usize {
+                        return Err(LevelParseError::ShortMapRow {
+                            line: line_no,
+                            col: chunks.col(),
+                            expected: w as usize,
+                            found: syms.len(),
+                        });
+                    }
+                    let (_, grid) = layers.last_mut().unwrap();
+                    for (col, sym) in syms {
+                        let idx = match legend.get(sym) {
+                            Some((idx, _)) => *idx,
+                            None if sym == EMPTY_SYMBOL => NO_TILE,
+                            None => {
+                                return Err(LevelParseError::UnknownMapSymbol {
+                                    line: line_no,
+                                    col,
+                                    sym: sym.to_string(),
+                                });
                             }
-                            _ => panic!("Unrecognized entity type in {line}"),
                         };
-                        let x =
-                            u16::from_str(chunks.next().expect("No x coord in start line {line}"))
-                                .expect("Couldn't parse x coord as u16 in {line}");
-                        let y =
-                            u16::from_str(chunks.next().expect("No y coord in start line {line}"))
-                                .expect("Couldn't parse y coord as u16 in {line}");
-                        starts.push((
-                            etype,
-                            Vec2 {
-                                x: x.into(),
-                                y: y.into(),
-                            },
-                        ));
-                    }
-                    State::Done => {
-                        panic!("Unexpected file content after parsing finished in {line}")
+                        grid.push(idx);
                     }
+                    rows += 1;
                 }
+                State::Starts => {
+                    let (col, etype) = chunks.next().unwrap();
+                    let etype = match etype {
+                        "player" => EntityType::Player,
+                        "enemy" => EntityType::Enemy,
+                        "door" => {
+                            let (_, to_room) = chunks
+                                .next()
+                                .ok_or_else(|| LevelParseError::bad_start(line_no, chunks.col()))?;
+                            let to_x = chunks.parse(LevelParseError::bad_start)?;
+                            let to_y = chunks.parse(LevelParseError::bad_start)?;
+                            EntityType::Door(to_room.to_string(), to_x, to_y)
+                        }
+                        _ => {
+                            return Err(LevelParseError::UnknownEntityType {
+                                line: line_no,
+                                col,
+                                etype: etype.to_string(),
+                            })
+                        }
+                    };
+                    let x: u16 = chunks.parse(LevelParseError::bad_start)?;
+                    let y: u16 = chunks.parse(LevelParseError::bad_start)?;
+                    starts.push((etype, tile_to_world((x.into(), y.into()))));
+                }
+                State::Done => {
+                    return Err(LevelParseError::TrailingContent {
+                        line: line_no,
+                        col: chunks.col(),
+                    });
+                }
+            }
+            if let Some((col, _)) = chunks.next() {
+                return Err(LevelParseError::TrailingContent { line: line_no, col });
             }
         }
-        assert_ne!(name, None);
-        let name = name.unwrap();
-        assert_ne!(dims, None);
-        let (w, h) = dims.unwrap();
-        assert!(!legend.is_empty());
-        assert_eq!(grid.len(), w as usize * h as usize);
-        let mut tiles: Vec<(u8, TileData)> = legend.into_values().collect();
-        tiles.sort_by_key(|(num, _)| *num);
-        Self {
-            name: name.to_string(),
-            grid: Grid::new(w as usize, h as usize, grid),
-            tileset: Tileset {
-                tiles: tiles.into_iter().map(|(_num, val)| val).collect(),
-            },
-            starts,
+        // errors about what's missing at the end point just past it
+        let (eof, col) = (last_line + 1, 1);
+        let (Some(name), Some((w, h))) = (name, dims) else {
+            return Err(LevelParseError::MissingHeader { line: eof, col });
+        };
+        if legend.is_empty() {
+            return Err(LevelParseError::MissingLegend { line: eof, col });
+        }
+        if rows != h as usize {
+            return Err(LevelParseError::WrongRowCount {
+                line: eof,
+                col,
+                expected: h as usize,
+                found: rows,
+            });
         }
+        let mut tiles: Vec<(u8, String, TileData)> = legend
+            .into_iter()
+            .map(|(sym, (num, data))| (num, sym, data))
+            .collect();
+        tiles.sort_by_key(|(num, _, _)| *num);
+        let (symbols, tiles) = tiles
+            .into_iter()
+            .map(|(_num, sym, data)| (sym, data))
+            .unzip();
+        if layers.is_empty() {
+            layers.push((DEFAULT_LAYER.to_string(), vec![]));
+        }
+        let layers = layers
+            .into_iter()
+            .map(|(name, grid)| Layer {
+                name,
+                grid: Grid::new(w as usize, h as usize, grid),
+            })
+            .collect();
+        Ok(Self {
+            name,
+            layers,
+            collision: collision.unwrap_or(0),
+            tileset: Tileset { tiles, symbols },
+            starts,
+            seed,
+        })
     }
+}
+
+impl Level {
     pub fn grid_height(&self) -> usize {
-        self.grid.height()
+        self.collision_grid().height()
     }
     pub fn grid_width(&self) -> usize {
-        self.grid.width()
+        self.collision_grid().width()
     }
     pub fn sprite_count(&self) -> usize {
-        self.grid.width() * self.grid.height()
+        self.layers().len() * self.grid_width() * self.grid_height()
     }
-    pub fn get_open_spaces(&self) -> Vec<Coord> {
+    /// The map's layers, in the order they're drawn.
+    pub fn layers(&self) -> &[Layer] {
+        &self.layers
+    }
+    fn collision_grid(&self) -> &Grid<u8> {
+        &self.layers[self.collision].grid
+    }
+    /// Tiles with none of the flags in `avoid`, where things can be put.
+    pub fn get_open_spaces(&self, avoid: TileFlags) -> Vec<Coord> {
         let mut open_spaces = Vec::new();
         for y in 0..self.grid_height() {
             for x in 0..self.grid_width() {
-                if let Some(tile) = self.get_tile(Vec2 {
-                    x: x as f32,
-                    y: y as f32,
-                }) {
-                    if !tile.solid {
-                        open_spaces.push((x, y));
-                    }
+                if !self.flags_at(x as i32, y as i32).intersects(avoid) {
+                    open_spaces.push((x, y));
                 }
             }
         }
         open_spaces
     }
-    pub fn render_into(&self, frend: &mut Renderer, offset: usize) -> usize {
+    /// Draws every layer into sprites `offset..offset + sprite_count()` of
+    /// group 0, later layers in front, with animated tiles showing their
+    /// frame for `time` seconds of simulation.
+    pub fn render_into(&self, frend: &mut Renderer, offset: usize, time: f32) -> usize {
         let (trfs, uvs) = frend.sprites_mut(0, offset..offset + self.sprite_count());
-        for (y, row) in self.grid.row_iter().enumerate() {
-            for (x, &tile_index) in row.iter().enumerate() {
-                let tile_data = &self.tileset[tile_index as usize];
-                trfs[offset + x + y * self.grid.width()] = Transform {
-                    x: (x as f32 * TILE_SZ as f32) + TILE_SZ as f32 / 2.0,
-                    y: (y as f32 * TILE_SZ as f32) + TILE_SZ as f32 / 2.0,
-                    w: TILE_SZ as u16,
-                    h: TILE_SZ as u16,
-                    rot: 0.0,
-                };
-                uvs[offset + x + y * self.grid.width()] = if tile_data.solid {
-                    WALL
-                } else {
-                    tile_data.sheet_region
-                };
+        let cells = self.grid_width() * self.grid_height();
+        for (layer_index, layer) in self.layers().iter().enumerate() {
+            for (y, row) in layer.grid().row_iter().enumerate() {
+                for (x, &tile_index) in row.iter().enumerate() {
+                    let sprite = layer_index * cells + x + y * self.grid_width();
+                    if tile_index == NO_TILE {
+                        trfs[sprite] = Transform::ZERO;
+                        uvs[sprite] = SheetRegion::ZERO;
+                        continue;
+                    }
+                    let tile_data = &self.tileset[tile_index as usize];
+                    let [screen_x, screen_y] = self.tile_screen_center((x, y));
+                    let TileOrientation {
+                        flip_x,
+                        flip_y,
+                        quarter_turns,
+                    } = tile_data.orientation;
+                    trfs[sprite] = Transform {
+                        x: screen_x,
+                        y: screen_y,
+                        w: TILE_SZ as u16,
+                        h: TILE_SZ as u16,
+                        // frenderer turns counterclockwise
+                        rot: -(quarter_turns as f32) * std::f32::consts::FRAC_PI_2,
+                    };
+                    let mut region = tile_data.sheet_region.unwrap_or(WALL);
+                    if let Some(animation) = &tile_data.animation {
+                        (region.x, region.y) = animation.frame_at(time);
+                    }
+                    if flip_x {
+                        region = region.flip_horizontal();
+                    }
+                    if flip_y {
+                        region = region.flip_vertical();
+                    }
+                    // smaller depths are nearer, so each layer covers the last
+                    let depth = region.depth.saturating_sub(layer_index as u16);
+                    uvs[sprite] = region.with_depth(depth);
+                }
             }
         }
         self.sprite_count()
     }
 
-    #[allow(dead_code)]
     pub fn name(&self) -> &str {
         &self.name
     }
+    pub fn seed(&self) -> Option<u64> {
+        self.seed
+    }
     pub fn starts(&self) -> &[(EntityType, Vec2)] {
         &self.starts
     }
+    /// Where the middle of tile `tile` is drawn.
+    pub fn tile_screen_center(&self, tile: Coord) -> [f32; 2] {
+        world_to_screen(cell_center(tile_to_world(tile)), self.grid_height())
+    }
+    /// The tile under the world point `pos`, if it's inside the level.
     pub fn get_tile(&self, pos: Vec2) -> Option<&TileData> {
-        self.grid
-            .get(pos.x as usize, pos.y as usize)
+        world_to_tile(pos).and_then(|tile| self.tile_at(tile))
+    }
+    /// The collision layer's tile at `x`, `y`, if there is one.
+    pub fn tile_at(&self, (x, y): Coord) -> Option<&TileData> {
+        self.collision_grid()
+            .get(x, y)
+            .filter(|&&t| t != NO_TILE)
             .map(|t| &self.tileset[*t as usize])
     }
+    /// Steps from every tile to the nearest of `sources` without crossing
+    /// tiles with any of the flags in `blocked_by`.
+    pub fn distance_map(&self, sources: &[Coord], blocked_by: TileFlags) -> Grid<Option<u32>> {
+        self.collision_grid().dijkstra_map(sources, |_, &t| {
+            t == NO_TILE || !self.tileset[t as usize].flags.intersects(blocked_by)
+        })
+    }
+    /// The flags of the collision layer's tile at `x`, `y`, where empty
+    /// cells are plain floor.  Everything outside the map counts as solid
+    /// so nothing can leave it.
+    pub fn flags_at(&self, x: i32, y: i32) -> TileFlags {
+        if x < 0 || y < 0 || x as usize >= self.grid_width() || y as usize >= self.grid_height() {
+            return TileFlags::SOLID;
+        }
+        self.tile_at((x as usize, y as usize))
+            .map_or(TileFlags::empty(), |tile| tile.flags)
+    }
+}
+
+impl std::fmt::Display for Level {
+    /// Writes the level in the same format `from_str` reads.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        const SEPARATOR: &str = "======";
+        write!(
+            f,
+            "{} {} {}",
+            self.name,
+            self.grid_width(),
+            self.grid_height()
+        )?;
+        match self.seed {
+            Some(seed) => writeln!(f, " seed={seed}")?,
+            None => writeln!(f)?,
+        }
+        writeln!(f, "{SEPARATOR}")?;
+        for (sym, tile) in self.tileset.symbols.iter().zip(&self.tileset.tiles) {
+            let flags = write_flags(tile.flags);
+            write!(f, "{sym} {flags}")?;
+            if let Some(region) = tile.sheet_region {
+                write!(f, " {} {} {} {}", region.x, region.y, region.w, region.h)?;
+            }
+            write!(f, "{}", write_orientation(tile.orientation))?;
+            if let Some(animation) = &tile.animation {
+                write!(f, "{}", write_animation(animation))?;
+            }
+            writeln!(f)?;
+        }
+        writeln!(f, "{SEPARATOR}")?;
+        // files with one unnamed layer are written the way they were read
+        let named = self.layers.len() > 1 || self.layers[0].name() != DEFAULT_LAYER;
+        for (index, layer) in self.layers.iter().enumerate() {
+            if named {
+                write!(f, "layer {}", layer.name())?;
+                if index == self.collision {
+                    write!(f, " collision")?;
+                }
+                writeln!(f)?;
+            }
+            for row in layer.grid.row_iter() {
+                let row: Vec<&str> = row
+                    .iter()
+                    .map(|&t| match t {
+                        NO_TILE => EMPTY_SYMBOL,
+                        t => self.tileset.symbols[t as usize].as_str(),
+                    })
+                    .collect();
+                writeln!(f, "{}", row.join(" "))?;
+            }
+        }
+        writeln!(f, "{SEPARATOR}")?;
+        for (etype, pos) in &self.starts {
+            match etype {
+                EntityType::Player => write!(f, "player")?,
+                EntityType::Enemy => write!(f, "enemy")?,
+                // level files can't place gold
+                EntityType::Gold => continue,
+                EntityType::Door(to_room, to_x, to_y) => write!(f, "door {to_room} {to_x} {to_y}")?,
+            }
+            writeln!(f, " {} {}", pos.x as u16, pos.y as u16)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    const SMALL: &str = "small 3 2
+===
+# s 0 0 16 16
+. o 0 0 16 16
+===
+# # #
+# . #
+===
+player 1 1
+door small 1 1 2 1
+";
+    fn err(s: &str) -> LevelParseError {
+        Level::from_str(s).err().expect("level should not parse")
+    }
+    fn assert_same(a: &Level, b: &Level) {
+        assert_eq!(a.name, b.name);
+        assert_eq!(a.layers.len(), b.layers.len());
+        for (la, lb) in a.layers.iter().zip(&b.layers) {
+            assert_eq!(la.name, lb.name);
+            assert_eq!(
+                la.grid.row_iter().collect::<Vec<_>>(),
+                lb.grid.row_iter().collect::<Vec<_>>()
+            );
+        }
+        assert_eq!(a.collision, b.collision);
+        assert_eq!(a.tileset.symbols, b.tileset.symbols);
+        assert_eq!(a.tileset.tiles.len(), b.tileset.tiles.len());
+        for (ta, tb) in a.tileset.tiles.iter().zip(&b.tileset.tiles) {
+            assert_eq!(ta.flags, tb.flags);
+            assert_eq!(ta.orientation, tb.orientation);
+            assert_eq!(ta.animation, tb.animation);
+            let coords = |r: SheetRegion| (r.sheet, r.depth, r.x, r.y, r.w, r.h);
+            assert_eq!(ta.sheet_region.map(coords), tb.sheet_region.map(coords));
+        }
+        assert_eq!(a.starts, b.starts);
+        assert_eq!(a.seed, b.seed);
+    }
+    #[test]
+    fn test_round_trip() {
+        let level = Level::from_str(SMALL).unwrap();
+        assert_same(&level, &Level::from_str(&level.to_string()).unwrap());
+        let seeded = Level::from_str(&SMALL.replace("small 3 2", "small 3 2 seed=7")).unwrap();
+        assert_eq!(seeded.seed(), Some(7));
+        assert_same(&seeded, &Level::from_str(&seeded.to_string()).unwrap());
+    }
+    #[test]
+    fn test_round_trip_content() {
+        // every level the game can reach, whatever its file is called
+        let cache = AssetCache::new("content").unwrap();
+        let world = load_world(&cache, crate::START_LEVEL).unwrap_or_else(|e| panic!("{e}"));
+        for (name, level) in &world {
+            let written = level.to_string();
+            let reread =
+                Level::from_str(&written).unwrap_or_else(|e| panic!("{name} after round trip:{e}"));
+            assert_same(level, &reread);
+            assert_eq!(written, reread.to_string());
+        }
+    }
+    #[test]
+    fn test_parse_ok() {
+        let level = Level::from_str(SMALL).unwrap();
+        assert_eq!(level.name(), "small");
+        assert_eq!((level.grid_width(), level.grid_height()), (3, 2));
+        assert_eq!(level.starts().len(), 2);
+        assert!(level
+            .get_tile(Vec2 { x: 0.0, y: 0.0 })
+            .unwrap()
+            .flags
+            .contains(TileFlags::SOLID));
+        assert!(!level
+            .get_tile(Vec2 { x: 1.0, y: 1.0 })
+            .unwrap()
+            .flags
+            .contains(TileFlags::SOLID));
+        assert!(!level
+            .get_tile(Vec2 { x: 1.9, y: 1.5 })
+            .unwrap()
+            .flags
+            .contains(TileFlags::SOLID));
+        assert!(level.get_tile(Vec2 { x: -0.5, y: 1.5 }).is_none());
+        assert!(level.flags_at(0, 0).contains(TileFlags::SOLID));
+        assert!(!level.flags_at(1, 1).contains(TileFlags::SOLID));
+        assert!(level.flags_at(-1, 1).contains(TileFlags::SOLID));
+        assert!(level.flags_at(3, 1).contains(TileFlags::SOLID));
+        assert!(level.flags_at(1, 2).contains(TileFlags::SOLID));
+    }
+    #[test]
+    fn test_flags() {
+        let level = Level::from_str(&SMALL.replace(". o", ". MdW")).unwrap();
+        let floor = level.flags_at(1, 1);
+        assert_eq!(
+            floor,
+            TileFlags::SLOW | TileFlags::DAMAGING | TileFlags::WATER
+        );
+        assert!(level.to_string().contains(". dmw "));
+        assert_same(&level, &Level::from_str(&level.to_string()).unwrap());
+        assert!(level.get_open_spaces(TileFlags::all()).is_empty());
+        assert_eq!(level.get_open_spaces(TileFlags::SOLID), [(1, 1)]);
+        assert_eq!(level.flags_at(1, 2), TileFlags::SOLID);
+    }
+    #[test]
+    fn test_looks() {
+        let level =
+            Level::from_str(&SMALL.replace("# s 0 0 16 16\n", "# s\nH s 32 0 16 16 FX r270\n"))
+                .unwrap();
+        let [wall, hedge, _] = &level.tileset.tiles[..] else {
+            panic!("expected three tiles");
+        };
+        assert!(wall.sheet_region.is_none());
+        assert_eq!(wall.orientation, TileOrientation::default());
+        assert_eq!(hedge.sheet_region.map(|r| r.x), Some(32));
+        let turned = TileOrientation {
+            flip_x: true,
+            flip_y: false,
+            quarter_turns: 3,
+        };
+        assert_eq!(hedge.orientation, turned);
+        let written = level.to_string();
+        assert!(
+            written.contains("\n# s\nH s 32 0 16 16 fx r270\n"),
+            "{written}"
+        );
+        assert_same(&level, &Level::from_str(&written).unwrap());
+        assert_eq!(
+            err(&SMALL.replace("# s 0 0 16 16", "# s 0 0 16 16 r45")),
+            LevelParseError::BadLegendEntry { line: 3, col: 15 }
+        );
+        assert_eq!(
+            err(&SMALL.replace("# s 0 0 16 16", "# s 0 0")),
+            LevelParseError::BadLegendEntry { line: 3, col: 8 }
+        );
+    }
+    #[test]
+    fn test_layers() {
+        let old = Level::from_str(SMALL).unwrap();
+        let names: Vec<&str> = old.layers().iter().map(Layer::name).collect();
+        assert_eq!(names, ["map"]);
+        assert!(!old.to_string().contains("\nlayer "));
+        let layered = SMALL.replace(
+            "===\n# # #\n# . #\n",
+            "t o 0 0 16 16\n===\nlayer floor\n. . .\n. . .\nlayer walls collision\n# # #\n# _ #\nlayer torches\n_ t _\n_ _ _\n",
+        );
+        let level = Level::from_str(&layered).unwrap();
+        let names: Vec<&str> = level.layers().iter().map(Layer::name).collect();
+        assert_eq!(names, ["floor", "walls", "torches"]);
+        assert_eq!(level.sprite_count(), 3 * 3 * 2);
+        // collisions come from the walls, with the gap in them open
+        assert_eq!(level.flags_at(1, 0), TileFlags::SOLID);
+        assert_eq!(level.flags_at(1, 1), TileFlags::empty());
+        assert_eq!(level.get_open_spaces(TileFlags::all()), [(1, 1)]);
+        assert_same(&level, &Level::from_str(&level.to_string()).unwrap());
+        assert_eq!(
+            err(&layered.replace("torches", "floor")),
+            LevelParseError::DuplicateLayer {
+                line: 13,
+                col: 7,
+                name: "floor".to_string()
+            }
+        );
+        assert_eq!(
+            err(&layered.replace("torches", "torches collision")),
+            LevelParseError::BadLayer { line: 13, col: 15 }
+        );
+        assert_eq!(
+            err(&layered.replace("layer torches\n_ t _\n", "layer torches\n")),
+            LevelParseError::WrongRowCount {
+                line: 15,
+                col: 1,
+                expected: 2,
+                found: 1
+            }
+        );
+        assert_eq!(
+            err(&layered.replace(". . .\n. . .\nlayer walls", ". . .\n  layer walls")),
+            LevelParseError::WrongRowCount {
+                line: 9,
+                col: 3,
+                expected: 2,
+                found: 1
+            }
+        );
+    }
+    #[test]
+    fn test_animated_tiles() {
+        let lava = SMALL.replace(". o 0 0 16 16", ". d 0 0 16 16 fx ANIM 0.25 16 0 32 0");
+        let level = Level::from_str(&lava).unwrap();
+        let floor = level.tile_at((1, 1)).unwrap();
+        let animation = floor.animation.as_ref().unwrap();
+        assert_eq!(animation.frames, [(0, 0), (16, 0), (32, 0)]);
+        assert_eq!(animation.frame_at(0.1), (0, 0));
+        assert_eq!(animation.frame_at(0.3), (16, 0));
+        assert_eq!(animation.frame_at(0.8), (0, 0));
+        assert!(level.tile_at((0, 0)).unwrap().animation.is_none());
+        let written = level.to_string();
+        assert!(
+            written.contains(". d 0 0 16 16 fx anim 0.25 16 0 32 0\n"),
+            "{written}"
+        );
+        assert_same(&level, &Level::from_str(&written).unwrap());
+        // needs a duration, at least one more frame and a region to start from
+        assert_eq!(
+            err(&SMALL.replace(". o 0 0 16 16", ". o 0 0 16 16 anim 0 16 0")),
+            LevelParseError::BadLegendEntry { line: 4, col: 20 }
+        );
+        assert_eq!(
+            err(&SMALL.replace(". o 0 0 16 16", ". o 0 0 16 16 anim 0.5")),
+            LevelParseError::BadLegendEntry { line: 4, col: 23 }
+        );
+        assert_eq!(
+            err(&SMALL.replace(". o 0 0 16 16", ". o 0 0 16 16 anim 0.5 16")),
+            LevelParseError::BadLegendEntry { line: 4, col: 26 }
+        );
+        assert_eq!(
+            err(&SMALL.replace(". o 0 0 16 16", ". o anim 0.5 16 0")),
+            LevelParseError::BadLegendEntry { line: 4, col: 5 }
+        );
+    }
+    #[test]
+    fn test_drawn_over_wall_is_wall() {
+        let level = Level::from_str(SMALL).unwrap();
+        let h = level.grid_height();
+        // an entity on the top row, which is all wall
+        let entity = tile_to_world((1, 0));
+        let drawn_at = world_to_screen(cell_center(entity), h);
+        assert_eq!(level.tile_screen_center((1, 0)), drawn_at);
+        assert!(level
+            .get_tile(cell_center(entity))
+            .unwrap()
+            .flags
+            .contains(TileFlags::SOLID));
+        assert!(level
+            .get_tile(screen_to_world(drawn_at, h))
+            .unwrap()
+            .flags
+            .contains(TileFlags::SOLID));
+        // the player starts on the floor below it, drawn lower down
+        let (_, player) = level.starts()[0];
+        assert!(!level
+            .get_tile(cell_center(player))
+            .unwrap()
+            .flags
+            .contains(TileFlags::SOLID));
+        assert!(world_to_screen(cell_center(player), h)[1] < drawn_at[1]);
+        assert_eq!(
+            level.tile_screen_center((1, 1)),
+            world_to_screen(cell_center(player), h)
+        );
+    }
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            err("===\n"),
+            LevelParseError::MissingHeader { line: 1, col: 1 }
+        );
+        assert_eq!(err(""), LevelParseError::MissingHeader { line: 1, col: 1 });
+        assert_eq!(
+            err("small 3 x\n"),
+            LevelParseError::BadDims { line: 1, col: 9 }
+        );
+        assert_eq!(
+            err("small 3 2 seed=x\n"),
+            LevelParseError::BadSeed { line: 1, col: 11 }
+        );
+        assert_eq!(
+            err(&SMALL.replace(". o", "# o")),
+            LevelParseError::DuplicateLegendSymbol {
+                line: 4,
+                col: 1,
+                sym: "#".to_string()
+            }
+        );
+        let legend = |n: usize| {
+            let tiles: String = (0..n).map(|i| format!("t{i} o 0 0 16 16\n")).collect();
+            format!("big 1 1\n===\n{tiles}===\nt0\n")
+        };
+        assert!(Level::from_str(&legend(255)).is_ok());
+        assert_eq!(
+            err(&legend(256)),
+            LevelParseError::TooManyTiles { line: 258, col: 1 }
+        );
+        assert_eq!(
+            err(&SMALL.replace(". o", ". q")),
+            LevelParseError::BadLegendEntry { line: 4, col: 3 }
+        );
+        assert_eq!(
+            err(&SMALL.replace(". o", ". sx")),
+            LevelParseError::BadLegendEntry { line: 4, col: 3 }
+        );
+        assert_eq!(
+            err(&SMALL.replace("# . #", "# ? #")),
+            LevelParseError::UnknownMapSymbol {
+                line: 7,
+                col: 3,
+                sym: "?".to_string()
+            }
+        );
+        assert_eq!(
+            err(&SMALL.replace("# . #", "# .")),
+            LevelParseError::ShortMapRow {
+                line: 7,
+                col: 4,
+                expected: 3,
+                found: 2
+            }
+        );
+        assert_eq!(
+            err(&SMALL.replace("# . #", "# . # #")),
+            LevelParseError::LongMapRow {
+                line: 7,
+                col: 7,
+                expected: 3,
+                found: 4
+            }
+        );
+        assert_eq!(
+            err(&SMALL.replace("# . #\n", "")),
+            LevelParseError::WrongRowCount {
+                line: 7,
+                col: 1,
+                expected: 2,
+                found: 1
+            }
+        );
+        assert_eq!(
+            err(&SMALL.replace("# . #\n", "# . #\n  # # #\n")),
+            LevelParseError::WrongRowCount {
+                line: 8,
+                col: 3,
+                expected: 2,
+                found: 3
+            }
+        );
+        assert_eq!(
+            err(&SMALL.replace("player", "wizard")),
+            LevelParseError::UnknownEntityType {
+                line: 9,
+                col: 1,
+                etype: "wizard".to_string()
+            }
+        );
+        assert_eq!(
+            err(&SMALL.replace("player 1 1", "player 1")),
+            LevelParseError::BadStart { line: 9, col: 9 }
+        );
+        assert_eq!(
+            err(&SMALL.replace("player 1 1", "player 1 1 1")),
+            LevelParseError::TrailingContent { line: 9, col: 12 }
+        );
+        assert_eq!(
+            err(&format!("{SMALL}===\nextra\n")),
+            LevelParseError::TrailingContent { line: 12, col: 1 }
+        );
+    }
 }
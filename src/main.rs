@@ -1,21 +1,27 @@
-use assets_manager::{asset::Png, AssetCache};
 // check out https://docs.rs/frenderer/latest/frenderer/ and https://github.com/JoeOsborn/frenderer/tree/main/examples for info on frenderer!
-use frenderer::{
-    input::{Input, Key},
-    sprites::{Camera2D, SheetRegion, Transform},
-    wgpu, Renderer,
-};
+use frenderer::{input::Input, sprites::SheetRegion};
 
-use std::fs::{self, OpenOptions};
-use std::io::{self, BufRead, BufReader, Write};
+use std::fs;
 use std::path::Path;
 
 extern crate rand;
-use rand::seq::SliceRandom;
-use rand::Rng;
+mod animation;
+mod camera;
+mod clock;
+mod collision;
+mod game;
 mod geom;
 mod grid;
-use geom::*;
+mod initials;
+mod leaderboard;
+mod render;
+mod replay;
+mod text;
+use clock::Clock;
+use game::{Ending, Game, GameEvent, GameState};
+use leaderboard::{Entry, Leaderboard};
+use render::GameView;
+use replay::{Controls, Replay};
 
 #[derive(Debug, PartialEq, Eq)]
 enum EntityType {
@@ -23,53 +29,154 @@ enum EntityType {
     Enemy,
     // which level, x in dest level, y in dest level
     Door(String, u16, u16),
+    #[allow(dead_code)]
     Gold,
 }
 
-#[derive(Clone, Copy)]
-struct Entity {
-    pos: Vec2,
-    dir: Vec2,
-    #[allow(dead_code)]
-    pattern: MovementPattern,
+bitflags::bitflags! {
+    /// What a tile does to things on or moving into it.  A tile with none
+    /// of these is plain floor.
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+    struct TileFlags: u8 {
+        /// Nothing can move into it.
+        const SOLID = 1 << 0;
+        /// Kills the player standing on it.
+        const DAMAGING = 1 << 1;
+        /// Halves the speed of anything standing on it, like mud or ice.
+        const SLOW = 1 << 2;
+        /// Can only be entered moving down, like a ledge.
+        const ONE_WAY = 1 << 3;
+        /// The player can wade through it but enemies can't.
+        const WATER = 1 << 4;
+        /// Reaching it wins the run.
+        const GOAL = 1 << 5;
+    }
 }
 
-#[derive(Clone, Copy)]
-enum MovementPattern {
-    Horizontal,
-    Vertical,
+/// How a tile's sprite is turned when it's drawn: flipped first, then
+/// rotated clockwise a number of quarter turns.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct TileOrientation {
+    flip_x: bool,
+    flip_y: bool,
+    quarter_turns: u8,
 }
 
-#[derive(Clone, Copy, Debug)]
-struct TileData {
-    solid: bool,
-    sheet_region: SheetRegion,
+/// Frames a tile cycles through, each shown for `frame_time` seconds.
+#[derive(Clone, Debug, PartialEq)]
+struct TileAnimation {
+    frame_time: f32,
+    // top left corners on the sheet; the first is the legend's own region
+    frames: Vec<(u16, u16)>,
 }
 
-mod level;
-use level::Level;
-struct Game {
-    level: Level,
-    player: Entity,
-    enemies: Vec<Entity>,
-    frame_counter: u32,
-    is_player_alive: bool,
-    start_time: std::time::Instant,
-    death_time: Option<std::time::Instant>,
-    golds: Vec<Vec2>,
-    score: u32,
+impl TileAnimation {
+    /// The corner of the frame showing `time` seconds into the animation.
+    fn frame_at(&self, time: f32) -> (u16, u16) {
+        let frame = (time.max(0.0) / self.frame_time) as usize;
+        self.frames[frame % self.frames.len()]
+    }
+}
+
+#[derive(Clone, Debug)]
+struct TileData {
+    flags: TileFlags,
+    // None if the legend left it out and the tile is drawn as a plain wall
+    sheet_region: Option<SheetRegion>,
+    orientation: TileOrientation,
+    animation: Option<TileAnimation>,
 }
 
+mod level;
 // Feel free to change this if you use a different tilesheet
 const TILE_SZ: usize = 16;
-const W: usize = 320;
-const H: usize = 240;
+const DT: f32 = 1.0 / 50.0;
+const MAX_STEPS_PER_FRAME: usize = 5;
+const START_LEVEL: &str = "level1";
+const LEADERBOARD_PATH: &str = "leaderboard.txt";
+
+/// Settings taken from the command line.
+struct Options {
+    // --seed N
+    seed: Option<u64>,
+    // --replay FILE: play back a recorded run instead of reading the keyboard
+    replay: Option<String>,
+    // --record FILE: where to save the replay of this run
+    record: String,
+    // --headless: simulate without opening a window
+    headless: bool,
+    // --steps N: how many steps to simulate headless
+    steps: Option<usize>,
+}
 
-const PLAYER: SheetRegion = SheetRegion::new(0, 16, 630, 0, 18, 16);
-const ENEMY: SheetRegion = SheetRegion::new(0, 16, 579, 0, 18, 16);
-const GOLD: SheetRegion = SheetRegion::new(0, 699, 193, 0, 13, 11);
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            seed: None,
+            replay: None,
+            record: "last.replay".to_string(),
+            headless: false,
+            steps: None,
+        }
+    }
+}
+
+impl Options {
+    fn from_args() -> Self {
+        let mut options = Options::default();
+        let mut args = std::env::args().skip(1);
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--seed" => {
+                    let seed = args.next().and_then(|s| s.parse().ok());
+                    if seed.is_none() {
+                        eprintln!("--seed needs a whole number after it");
+                        std::process::exit(2);
+                    }
+                    options.seed = seed;
+                }
+                "--steps" => {
+                    let steps = args.next().and_then(|s| s.parse().ok());
+                    if steps.is_none() {
+                        eprintln!("--steps needs a whole number after it");
+                        std::process::exit(2);
+                    }
+                    options.steps = steps;
+                }
+                "--headless" => options.headless = true,
+                "--replay" | "--record" => {
+                    let Some(path) = args.next() else {
+                        eprintln!("{arg} needs a file name after it");
+                        std::process::exit(2);
+                    };
+                    if arg == "--replay" {
+                        options.replay = Some(path);
+                    } else {
+                        options.record = path;
+                    }
+                }
+                _ => {
+                    eprintln!("Unrecognized argument {arg}");
+                    std::process::exit(2);
+                }
+            }
+        }
+        options
+    }
+}
 
 fn main() {
+    let options = Options::from_args();
+    let playback = options.replay.as_ref().map(|path| {
+        fs::read_to_string(path)
+            .map_err(|e| e.to_string())
+            .and_then(|text| text.parse::<Replay>().map_err(|e| e.to_string()))
+            .unwrap_or_else(|e| {
+                eprintln!("{path}:{e}");
+                std::process::exit(1);
+            })
+    });
+    let record_path = options.record;
     #[cfg(not(target_arch = "wasm32"))]
     let source =
         assets_manager::source::FileSystem::new("content").expect("Couldn't load resources");
@@ -77,6 +184,38 @@ fn main() {
     let source = assets_manager::source::Embedded::from(assets_manager::source::embed!("content"));
     let cache = assets_manager::AssetCache::with_source(source);
 
+    let mut world = level::load_world(&cache, START_LEVEL).unwrap_or_else(|e| {
+        eprintln!("{e}");
+        std::process::exit(1);
+    });
+    let level = world.remove(START_LEVEL).unwrap();
+    // A replay has to use the seed it was recorded with.  Otherwise a seed
+    // from the command line beats one from the level, and without either
+    // every run is different.
+    let seed = playback
+        .as_ref()
+        .map(|replay| replay.seed)
+        .or(options.seed)
+        .or(level.seed())
+        .unwrap_or_else(|| {
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map_or(0, |t| t.as_nanos() as u64)
+        });
+    println!("Seed: {seed}");
+    let leaderboard = Leaderboard::load(Path::new(LEADERBOARD_PATH)).unwrap_or_else(|e| {
+        eprintln!("Couldn't read {LEADERBOARD_PATH}: {e}");
+        Leaderboard::default()
+    });
+    let mut game = Game::new(level, seed)
+        .with_levels(world)
+        .with_leaderboard(leaderboard);
+
+    if options.headless {
+        run_headless(game, playback.as_ref(), options.steps);
+        return;
+    }
+
     let drv = frenderer::Driver::new(
         winit::window::WindowBuilder::new()
             .with_title("test")
@@ -84,23 +223,47 @@ fn main() {
         Some((1024, 768)),
     );
 
-    const DT: f32 = 1.0 / 50.0;
+    // a replay skips the title screen
+    if playback.is_some() {
+        game.state = GameState::Playing;
+    }
     let mut input = Input::default();
 
-    let mut now = frenderer::clock::Instant::now();
-    let mut acc = 0.0;
+    let mut clock = Clock::new(DT, MAX_STEPS_PER_FRAME);
     drv.run_event_loop::<(), _>(
         move |window, mut frend| {
-            let game = Game::new(&mut frend, &cache);
-            (window, game, frend)
+            let view = GameView::new(&mut frend, &cache, &game);
+            let recording = Replay::new(game.seed);
+            // a score waiting for another session to finish with the
+            // leaderboard
+            let unsaved: Option<Entry> = None;
+            (window, game, view, frend, recording, unsaved)
         },
-        move |event, target, (window, ref mut game, ref mut frend)| {
+        move |event,
+              target,
+              (
+            window,
+            ref mut game,
+            ref mut view,
+            ref mut frend,
+            ref mut recording,
+            ref mut unsaved,
+        )| {
             use winit::event::{Event, WindowEvent};
             match event {
                 Event::WindowEvent {
                     event: WindowEvent::CloseRequested,
                     ..
                 } => {
+                    // Runs that already ended were saved then
+                    if playback.is_none() && recording.outcome.is_none() {
+                        recording.outcome = Some(game.outcome());
+                        save_replay(&record_path, recording);
+                    }
+                    save_score(game, unsaved);
+                    if unsaved.is_some() {
+                        eprintln!("Couldn't save score: {LEADERBOARD_PATH} is still locked");
+                    }
                     target.exit();
                 }
                 Event::WindowEvent {
@@ -116,34 +279,53 @@ fn main() {
                     event: WindowEvent::RedrawRequested,
                     ..
                 } => {
-                    let elapsed = now.elapsed().as_secs_f32();
-                    // You can add the time snapping/death spiral prevention stuff here if you want.
-                    // I'm not using it here to keep the starter code small.
-                    acc += elapsed;
-                    now = std::time::Instant::now();
-                    // While we have time to spend
-                    while acc >= DT {
-                        // simulate a frame
-                        acc -= DT;
-                        game.simulate(&input, DT);
+                    for _ in 0..clock.tick() {
+                        let mut controls = Controls::default();
+                        if game.state.is_running() {
+                            controls = match &playback {
+                                Some(replay) => match replay.steps().get(recording.steps().len()) {
+                                    Some(&controls) => controls,
+                                    None => finish_replay(replay, game),
+                                },
+                                None => Controls::from_input(&input),
+                            };
+                            recording.record(controls);
+                        }
+                        let event = game.update(&input, controls, DT);
                         input.next_frame();
-                    }
-                    game.render(frend);
-                    frend.render();
-                    window.request_redraw();
-
-                    if !game.is_player_alive {
-                        if let Some(death_time) = game.death_time {
-                            if death_time.elapsed().as_secs() >= 3 {
-                                println!("You Lose! You collected {} gold", game.score);
-                                target.exit();
+                        match event {
+                            Some(GameEvent::RunStarted) => *recording = Replay::new(game.seed),
+                            Some(GameEvent::RunEnded(ending)) => {
+                                // A replay is over once its run is
+                                if let Some(replay) = &playback {
+                                    finish_replay(replay, game);
+                                }
+                                match ending {
+                                    Ending::Won => {
+                                        println!("You Win! You collected {} gold", game.score)
+                                    }
+                                    Ending::Lost => {
+                                        println!("You Lose! You collected {} gold", game.score)
+                                    }
+                                }
+                                recording.outcome = Some(game.outcome());
+                                save_replay(&record_path, recording);
+                            }
+                            Some(GameEvent::Scored(mut entry)) => {
+                                entry.timestamp = std::time::SystemTime::now()
+                                    .duration_since(std::time::UNIX_EPOCH)
+                                    .ok()
+                                    .map(|t| t.as_secs());
+                                *unsaved = Some(entry);
                             }
+                            Some(GameEvent::Quit) => target.exit(),
+                            None => {}
                         }
-                    } else if game.start_time.elapsed().as_secs() >= 60 {
-                        println!("You Win! You collected {} gold", game.score);
-                        handle_win(game.score);
-                        target.exit();
                     }
+                    save_score(game, unsaved);
+                    view.render(game, clock.alpha(), frend);
+                    frend.render();
+                    window.request_redraw();
                 }
                 event => {
                     input.process_input_event(&event);
@@ -154,407 +336,76 @@ fn main() {
     .expect("event loop error");
 }
 
-fn handle_win(score: u32) {
-    let initials = prompt_for_initials();
-    if let Err(e) = save_score(&initials, score) {
-        eprintln!("Error saving score: {}", e);
-        return;
-    }
-
-    match read_leaderboard() {
-        Ok(leaderboard) => display_leaderboard(&leaderboard),
-        Err(e) => eprintln!("Error reading leaderboard: {}", e),
-    }
-}
-
-fn prompt_for_initials() -> String {
-    println!("Enter your initials:");
-    let mut initials = String::new();
-    io::stdin().read_line(&mut initials).expect("Failed to read line");
-    initials.trim().to_uppercase()
-}
-
-fn display_leaderboard(leaderboard: &[(String, u32)]) {
-    println!("Leaderboard");
-    println!("----------------");
-    println!("Initials\t\tScore");
-    for (initials, score) in leaderboard {
-        println!("{}\t\t\t{}", initials, score);
-    }
-}
-
-fn read_leaderboard() -> io::Result<Vec<(String, u32)>> {
-    let path = Path::new("leaderboard.txt");
-    let file = fs::File::open(path)?;
-    let buf_reader = BufReader::new(file);
-    let mut leaderboard = vec![];
-
-    for line in buf_reader.lines() {
-        let line = line?;
-        let parts: Vec<&str> = line.split(',').collect();
-        if parts.len() == 2 {
-            if let Ok(score) = parts[1].parse::<u32>() {
-                leaderboard.push((parts[0].to_string(), score));
-            }
+/// Runs the game without a window, fed by `playback` if there is one and
+/// otherwise with no keys held, and prints how it went.
+fn run_headless(mut game: Game, playback: Option<&Replay>, steps: Option<usize>) {
+    let Some(steps) = steps.or(playback.map(|replay| replay.steps().len())) else {
+        eprintln!("--headless needs --steps N or --replay FILE");
+        std::process::exit(2);
+    };
+    for step in 0..steps {
+        // Replays were recorded past the end of the run, so only stop
+        // early when nothing is being checked.
+        if playback.is_none() && game.ending().is_some() {
+            break;
         }
+        let controls = playback
+            .and_then(|replay| replay.steps().get(step).copied())
+            .unwrap_or_default();
+        game.simulate(controls, DT);
     }
-
-    // Sort the leaderboard by score in descending order
-    leaderboard.sort_by(|a, b| b.1.cmp(&a.1));
-
-    Ok(leaderboard)
-}
-
-fn save_score(initials: &str, score: u32) -> io::Result<()> {
-    let path = Path::new("leaderboard.txt");
-    let mut file = OpenOptions::new()
-        .write(true)
-        .append(true)
-        .create(true)
-        .open(path)?;
-
-    writeln!(file, "{},{}", initials, score)
-}
-
-impl Entity {
-    pub fn new_enemy(pos: Vec2, pattern: MovementPattern) -> Self {
-        let initial_dir = match pattern {
-            MovementPattern::Horizontal => Vec2 { x: 1.0, y: 0.0 },
-            MovementPattern::Vertical => Vec2 { x: 0.0, y: 1.0 },
-        };
-        Entity {
-            pos,
-            dir: initial_dir,
-            pattern,
+    let ending = match game.ending() {
+        Some(Ending::Won) => "won",
+        Some(Ending::Lost) => "lost",
+        None => "still playing",
+    };
+    println!("{ending} after {:.2}s: {}", game.time, game.outcome());
+    if let Some(replay) = playback {
+        if steps == replay.steps().len() {
+            finish_replay(replay, &game);
         }
     }
 }
 
-impl Game {
-    fn new(renderer: &mut Renderer, cache: &AssetCache) -> Self {
-        let tile_handle = cache
-            .load::<Png>("tilesheet")
-            .expect("Couldn't load tilesheet img");
-        let tile_img = tile_handle.read().0.to_rgba8();
-        let tile_tex = renderer.create_array_texture(
-            &[&tile_img],
-            wgpu::TextureFormat::Rgba8UnormSrgb,
-            tile_img.dimensions(),
-            Some("tiles-sprites"),
-        );
-        let level = Level::from_str(
-            &cache
-                .load::<String>("level1")
-                .expect("Couldn't access level1.txt")
-                .read(),
-        );
-        let camera = Camera2D {
-            screen_pos: [0.0, 0.0],
-            screen_size: [W as f32, H as f32],
-        };
-        let sprite_estimate = level.sprite_count() + level.starts().len();
-        renderer.sprite_group_add(
-            &tile_tex,
-            vec![Transform::ZERO; sprite_estimate],
-            vec![SheetRegion::ZERO; sprite_estimate],
-            camera,
-        );
-        let player_start = *level
-            .starts()
-            .iter()
-            .find(|(t, _)| *t == EntityType::Player)
-            .map(|(_, ploc)| ploc)
-            .expect("Start level doesn't put the player anywhere");
-        let mut game = Game {
-            level,
-            player: Entity {
-                pos: Vec2 { x: 0.0, y: 0.0 },
-                dir: Vec2 { x: 0.0, y: 0.0 },
-                pattern: MovementPattern::Horizontal,
-            },
-            enemies: Vec::new(),
-            frame_counter: 0,
-            is_player_alive: true,
-            start_time: std::time::Instant::now(),
-            death_time: None,
-            golds: Vec::new(),
-            score: 0,
-        };
-        game.enter_level(player_start);
-        game.spawn_enemies();
-        game.spawn_gold(50);
-
-        game
-    }
-    fn enter_level(&mut self, player_pos: Vec2) {
-        // TODO point: move player to player_pos, delete all enemies and doors,
-        // create an entity for each start in level
-        self.player.pos = player_pos;
-
-        for (etype, pos) in self.level.starts().iter() {
-            match etype {
-                EntityType::Player => {}
-                EntityType::Door(_rm, _x, _y) => {
-                    println!("Would add a door to room: {} at x: {}, y: {}", _rm, _x, _y);
-                }
-                EntityType::Enemy => {
-                    println!("Would spawn an enemy at position: {:?}", pos);
-                }
-                _ => {
-                    // Ignore other types, such as Gold, as they are handled separately or not applicable here.
-                }
-            }                      
-        }
-    }
-
-
-    fn spawn_gold(&mut self, gold_count: i32) {
-        let open_spaces = self.level.get_open_spaces();
-        let mut rng = rand::thread_rng();
-
-        // Filter open spaces to exclude those occupied by enemies or the player
-        let available_spaces: Vec<_> = open_spaces
-            .into_iter()
-            .filter(|pos| {
-                self.player.pos != Vec2 { x: pos.0 as f32, y: pos.1 as f32 }
-            })
-            .collect();
-
-        // Randomly choose locations from available spaces
-        for _ in 0..gold_count {
-            if let Some(&position) = available_spaces.choose(&mut rng) {
-                // Use `position` directly here
-                self.golds.push(Vec2 { x: position.0 as f32, y: position.1 as f32 });
-            }
-        }
-
+fn save_replay(path: &str, replay: &Replay) {
+    if let Err(e) = fs::write(path, replay.to_string()) {
+        eprintln!("Couldn't save replay to {path}: {e}");
+    } else {
+        println!("Saved replay to {path}");
     }
+}
 
-    fn spawn_enemies(&mut self) {
-        let open_spaces = self.level.get_open_spaces();
-        // remove spaces that are too close to the player
-        let open_spaces = open_spaces
-            .iter()
-            .filter(|&pos| {
-                let dx = (self.player.pos.x - pos.0 as f32).abs();
-                let dy = (self.player.pos.y - pos.1 as f32).abs();
-                dx > 15.0 || dy > 15.0
-            })
-            .copied()
-            .collect::<Vec<_>>();
-        let mut rng = rand::thread_rng();
-        let enemy_count = rng.gen_range(1..=2);
-
-        for _ in 0..enemy_count {
-            if let Some(&position) = open_spaces.choose(&mut rng) {
-                let pattern = if rng.gen() {
-                    MovementPattern::Horizontal
-                } else {
-                    MovementPattern::Vertical
-                };
-                let enemy = Entity::new_enemy(
-                    Vec2 {
-                        x: position.0 as f32,
-                        y: position.1 as f32,
-                    },
-                    pattern,
-                );
-                self.enemies.push(enemy);
-            }
-        }
-    }
-
-    fn update_gold(&mut self) {
-        let player_size = 0.25;
-        let gold_size = 0.25; // Adjust this as necessary
-
-        // Detect golds to remove
-        let to_remove: Vec<Vec2> = self.golds.iter().filter_map(|gold_pos| {
-            if Self::check_collision(self.player.pos, player_size, *gold_pos, gold_size) {
-                Some(*gold_pos)
-            } else {
-                None
-            }
-        }).collect();
-
-        // Remove golds that collided
-        self.golds.retain(|gold_pos| !to_remove.contains(gold_pos));
-
-        // Spawn a new gold if needed
-        if self.golds.len() < 50 {
-            self.spawn_gold(1);
-            self.score += 1;
-        }
-
-    }
-
-    
-    fn check_collision(a_pos: Vec2, a_size: f32, b_pos: Vec2, b_size: f32) -> bool {
-        let a_half_size = a_size / 2.0;
-        let b_half_size = b_size / 2.0;
-    
-        // Check for overlap in the x-axis
-        let x_overlap = (a_pos.x - b_pos.x).abs() < (a_half_size + b_half_size);
-        // Check for overlap in the y-axis
-        let y_overlap = (a_pos.y - b_pos.y).abs() < (a_half_size + b_half_size);
-    
-        x_overlap && y_overlap
-    }
-    
-    fn calculate_total_sprites_needed(&self) -> usize {
-        let level_tiles = self.level.grid_width() * self.level.grid_height();
-        let entity_count = 1 + self.enemies.len() + self.golds.len();
-
-        let other_entities_count = 0;
-        level_tiles + entity_count + other_entities_count
-    }
-
-    // fn get_enemies_count(&self) -> usize {
-    //     self.enemies.len()
-    // }
-
-    // // Similarly, implement this based on your game's structure
-    // fn get_other_dynamic_elements_count(&self) -> usize {
-    //     // For example, counting items or interactive objects
-    //     0 // Placeholder, adjust as necessary
-    // }
-
-    // fn sprite_count(&self) -> usize {
-    //     self.level.sprite_count()
-    // }
-
-    fn render(&mut self, frend: &mut Renderer) {
-        let total_sprites_needed = self.calculate_total_sprites_needed();
-
-        frend.sprite_group_resize(0, total_sprites_needed);
-
-        self.level.render_into(frend, 0);
-
-        let (sprite_posns, sprite_gfx) = frend.sprites_mut(0, 0..total_sprites_needed);
-
-        let player_sprite_index = self.level.sprite_count();
-
-        if let Some(player_sprite) = sprite_posns.get_mut(player_sprite_index) {
-            player_sprite.x = (self.player.pos.x as f32) * TILE_SZ as f32 + TILE_SZ as f32 / 2.0;
-            player_sprite.y = ((self.level.grid_height() as f32) - self.player.pos.y as f32)
-                * TILE_SZ as f32
-                - TILE_SZ as f32 / 2.0;
-            player_sprite.w = (TILE_SZ as u16) / 2;
-            player_sprite.h = (TILE_SZ as u16) / 2;
-            player_sprite.rot = if self.is_player_alive { 0.0 } else { 90.0 };
+/// Compares the end of a played-back run with how the recording ended.
+fn finish_replay(replay: &Replay, game: &Game) -> ! {
+    let outcome = game.outcome();
+    match &replay.outcome {
+        Some(expected) if *expected == outcome => {
+            println!("Replay matches: {outcome}");
+            std::process::exit(0);
         }
-
-        if let Some(player_sprite_gfx) = sprite_gfx.get_mut(player_sprite_index) {
-            *player_sprite_gfx = PLAYER;
+        Some(expected) => {
+            eprintln!("Replay diverged!\nexpected: {expected}\n     got: {outcome}");
+            std::process::exit(1);
         }
-
-        // for (index, enemy) in self.enemies.iter().enumerate() {
-        //     let sprite_index = self.level.sprite_count() + 1 + index;
-        //     if let Some(enemy_sprite) = sprite_posns.get_mut(sprite_index) {
-        //         enemy_sprite.x = (enemy.pos.x as f32) * TILE_SZ as f32 + TILE_SZ as f32 / 2.0;
-        //         enemy_sprite.y = ((self.level.grid_height() as f32) - enemy.pos.y as f32)
-        //             * TILE_SZ as f32
-        //             - TILE_SZ as f32 / 2.0;
-        //         enemy_sprite.w = TILE_SZ as u16;
-        //         enemy_sprite.h = TILE_SZ as u16;
-        //         enemy_sprite.rot = 0.0;
-        //     }
-
-        //     if let Some(enemy_sprite_gfx) = sprite_gfx.get_mut(sprite_index) {
-        //         *enemy_sprite_gfx = ENEMY;
-        //     }
-        // }
-
-        for (index, gold_pos) in self.golds.iter().enumerate() {
-            let sprite_index = self.level.sprite_count() + index + 1; // Adjust index based on total_sprites_needed calculation
-            if let Some(gold_sprite) = sprite_posns.get_mut(sprite_index) {
-                gold_sprite.x = (gold_pos.x as f32) * TILE_SZ as f32 + TILE_SZ as f32 / 2.0;
-                gold_sprite.y = ((self.level.grid_height() as f32) - gold_pos.y as f32) * TILE_SZ as f32 - TILE_SZ as f32 / 2.0;
-                gold_sprite.w = TILE_SZ as u16 / 3;
-                gold_sprite.h = TILE_SZ as u16 / 3;
-                gold_sprite.rot = 0.0;
-            }
-    
-            if let Some(gold_sprite_gfx) = sprite_gfx.get_mut(sprite_index) {
-                *gold_sprite_gfx = GOLD;
-            }
+        None => {
+            println!("Replay finished (no recorded outcome to check): {outcome}");
+            std::process::exit(0);
         }
     }
+}
 
-    fn simulate(&mut self, input: &Input, dt: f32) {
-        if self.is_player_alive {
-
-            let speed = 5.0;
-
-            let dx = input.key_axis(Key::ArrowLeft, Key::ArrowRight);
-            let dy = input.key_axis(Key::ArrowUp, Key::ArrowDown);
-
-            self.player.pos.x += dx as f32 * speed * dt;
-            self.player.pos.y += dy as f32 * speed * dt;
-
-            let enemy_dt: f32 = 1.0 / 2.0;
-            let enemy_speed = 2.0;
-
-            self.frame_counter += 1;
-            for enemy in &mut self.enemies {
-
-                // make enemy point towards player
-                let difference_in_x = self.player.pos.x - enemy.pos.x;
-                let difference_in_y = self.player.pos.y - enemy.pos.y;
-
-                // turn towards player, x direction
-                if difference_in_x > 0.0 {
-                    enemy.dir.x = 1.0;
-                } else if difference_in_x < 0.0 {
-                    enemy.dir.x = -1.0;
-                } else {
-                    enemy.dir.x = 0.0;
-                }
-                // turn towards player, y direction
-                if difference_in_y > 0.0 {
-                    enemy.dir.y = 1.0;
-                } else if difference_in_y < 0.0 {
-                    enemy.dir.y = -1.0;
-                } else {
-                    enemy.dir.y = 0.0;
-                }
-
-                let new_x = (enemy.pos.x as f32 + enemy.dir.x as f32 * enemy_speed * enemy_dt)
-
-                    .round() as f32;
-                let new_y = (enemy.pos.y as f32 + enemy.dir.y as f32 * enemy_speed * enemy_dt)
-                    .round() as f32;
-                if new_x >= 0.0
-                    && new_x < self.level.grid_width() as f32
-                    && new_y >= 0.0
-                    && new_y < self.level.grid_height() as f32
-                {
-                    let dest = Vec2 { x: new_x, y: new_y };
-                    if let Some(tile) = self.level.get_tile(dest) {
-                        if !tile.solid {
-                            enemy.pos.x = new_x as f32;
-                            enemy.pos.y = new_y as f32;
-                        } else {
-                            enemy.dir.x = -enemy.dir.x;
-                            enemy.dir.y = -enemy.dir.y;
-                        }
-                    }
-                } else {
-                    enemy.dir.x = -enemy.dir.x;
-                    enemy.dir.y = -enemy.dir.y;
-                }
-            }
-            self.frame_counter = 0;
-
-            self.update_gold();
-
-            // for enemy in &self.enemies {
-            //     if self.player.pos == enemy.pos {
-            //         self.is_player_alive = false;
-            //         self.death_time = Some(std::time::Instant::now());
-            //         break;
-            //     }
-            // }
-        }
+/// Tries once to save the run in `unsaved` to the leaderboard file and
+/// keep `game`'s copy of the leaderboard in step with it.  The run stays
+/// in `unsaved` if another session has the file locked, to be tried again
+/// on a later frame.
+fn save_score(game: &mut Game, unsaved: &mut Option<Entry>) {
+    let Some(entry) = unsaved else {
+        return;
+    };
+    match Leaderboard::try_record(Path::new(LEADERBOARD_PATH), entry) {
+        Ok(Some(leaderboard)) => game.leaderboard = leaderboard,
+        Ok(None) => return,
+        Err(e) => eprintln!("Error saving score: {}", e),
     }
+    *unsaved = None;
 }
@@ -0,0 +1,275 @@
+use crate::animation::{AnimState, AnimationSet, Animations};
+use crate::camera::CameraController;
+use crate::game::{Game, GameState, ENEMY_SIZE, PLAYER_SIZE};
+use crate::geom::{cell_center, world_to_screen};
+use crate::initials::InitialsEntry;
+use crate::leaderboard::{Entry, Leaderboard};
+use crate::text::{Text, TILESHEET_FONT};
+use crate::{DT, TILE_SZ};
+use assets_manager::{asset::Png, AssetCache};
+use frenderer::{
+    sprites::{SheetRegion, Transform},
+    wgpu, Renderer,
+};
+
+const W: usize = 320;
+const H: usize = 240;
+
+const PLAYER: SheetRegion = SheetRegion::new(0, 16, 630, 0, 18, 16);
+const ENEMY: SheetRegion = SheetRegion::new(0, 16, 579, 0, 18, 16);
+const GOLD: SheetRegion = SheetRegion::new(0, 699, 193, 0, 13, 11);
+const DOOR: SheetRegion = SheetRegion::new(0, 545, 1, 8, 14, 15);
+
+// The simulation time `alpha` of the way from the previous step to the
+// latest one.
+fn sim_time(game: &Game, alpha: f32) -> f32 {
+    game.time + (alpha - 1.0) * DT
+}
+
+/// Draws a [`Game`] into frenderer's sprite group 0, with the HUD and
+/// other text in a second group on top.  The game itself never touches
+/// the renderer.
+pub struct GameView {
+    text: Text,
+    player_anims: AnimationSet,
+    enemy_anims: AnimationSet,
+    gold_anims: AnimationSet,
+    camera: CameraController,
+    // the level and sim time the camera last followed the player in
+    camera_level: String,
+    camera_time: f32,
+}
+
+impl GameView {
+    pub fn new(renderer: &mut Renderer, cache: &AssetCache, game: &Game) -> Self {
+        let tile_handle = cache
+            .load::<Png>("tilesheet")
+            .expect("Couldn't load tilesheet img");
+        let tile_img = tile_handle.read().0.to_rgba8();
+        let tile_tex = renderer.create_array_texture(
+            &[&tile_img],
+            wgpu::TextureFormat::Rgba8UnormSrgb,
+            tile_img.dimensions(),
+            Some("tiles-sprites"),
+        );
+        let camera = CameraController::new([W as f32, H as f32]);
+        let sprite_estimate = Self::calculate_total_sprites_needed(game);
+        renderer.sprite_group_add(
+            &tile_tex,
+            vec![Transform::ZERO; sprite_estimate],
+            vec![SheetRegion::ZERO; sprite_estimate],
+            camera.camera(),
+        );
+        let text = Text::new(renderer, cache, TILESHEET_FONT, [W as f32, H as f32]);
+        // without animations everything still shows up, just standing still
+        let animations = Animations::load(cache, "animations").unwrap_or_else(|e| {
+            eprintln!("{e}");
+            Animations::default()
+        });
+        let anims = |entity, still| {
+            animations
+                .get(entity)
+                .cloned()
+                .unwrap_or_else(|| AnimationSet::still(still))
+        };
+        GameView {
+            text,
+            player_anims: anims("player", PLAYER),
+            enemy_anims: anims("enemy", ENEMY),
+            gold_anims: anims("gold", GOLD),
+            camera,
+            camera_level: String::new(),
+            camera_time: 0.0,
+        }
+    }
+
+    fn calculate_total_sprites_needed(game: &Game) -> usize {
+        let level_tiles = game.level.sprite_count();
+        let entity_count = 1 + game.enemies.len() + game.golds.len();
+
+        let other_entities_count = game.doors.len();
+        level_tiles + entity_count + other_entities_count
+    }
+
+    /// Draws whichever screen `game` is on, with moving things `alpha` of
+    /// the way from their previous simulation step to the latest one.
+    pub fn render(&mut self, game: &Game, alpha: f32, frend: &mut Renderer) {
+        // nothing moves between steps that weren't simulated
+        let alpha = if game.state.is_running() { alpha } else { 1.0 };
+        match &game.state {
+            GameState::Won(entry) => self.render_initials(entry, game.score, frend),
+            GameState::Leaderboard => {
+                self.render_leaderboard(&game.leaderboard, game.start_level(), frend)
+            }
+            GameState::Title | GameState::Playing | GameState::Paused | GameState::Dead => {
+                self.follow_player(game, alpha, frend);
+                self.render_world(game, alpha, frend);
+                self.render_hud(game, frend);
+            }
+        }
+    }
+
+    /// Moves the camera after the player, going by simulation time so it
+    /// holds still while the game is paused.
+    fn follow_player(&mut self, game: &Game, alpha: f32, frend: &mut Renderer) {
+        let target = world_to_screen(
+            cell_center(game.player.pos_at(alpha)),
+            game.level.grid_height(),
+        );
+        let bounds = [
+            (game.level.grid_width() * TILE_SZ) as f32,
+            (game.level.grid_height() * TILE_SZ) as f32,
+        ];
+        let time = sim_time(game, alpha);
+        let dt = time - self.camera_time;
+        // a new level or a new run starts with the player in view
+        if game.level.name() != self.camera_level || dt < 0.0 {
+            self.camera.snap_to(target, bounds);
+            self.camera_level = game.level.name().to_string();
+        } else {
+            self.camera.update(target, bounds, dt);
+        }
+        self.camera_time = time;
+        frend.sprite_group_set_camera(0, self.camera.camera());
+    }
+
+    fn render_world(&mut self, game: &Game, alpha: f32, frend: &mut Renderer) {
+        let total_sprites_needed = Self::calculate_total_sprites_needed(game);
+
+        frend.sprite_group_resize(0, total_sprites_needed);
+
+        let time = sim_time(game, alpha);
+        game.level.render_into(frend, 0, time);
+
+        let (sprite_posns, sprite_gfx) = frend.sprites_mut(0, 0..total_sprites_needed);
+
+        let player_sprite_index = game.level.sprite_count();
+        let grid_height = game.level.grid_height();
+
+        if let Some(player_sprite) = sprite_posns.get_mut(player_sprite_index) {
+            let [x, y] = world_to_screen(cell_center(game.player.pos_at(alpha)), grid_height);
+            player_sprite.x = x;
+            player_sprite.y = y;
+            player_sprite.w = (PLAYER_SIZE * TILE_SZ as f32) as u16;
+            player_sprite.h = (PLAYER_SIZE * TILE_SZ as f32) as u16;
+            player_sprite.rot = 0.0;
+        }
+
+        if let Some(player_sprite_gfx) = sprite_gfx.get_mut(player_sprite_index) {
+            let player = &game.player;
+            *player_sprite_gfx = self
+                .player_anims
+                .region(player.anim, time - player.anim_start);
+        }
+
+        for (index, enemy) in game.enemies.iter().enumerate() {
+            let sprite_index = player_sprite_index + 1 + index;
+            if let Some(enemy_sprite) = sprite_posns.get_mut(sprite_index) {
+                let [x, y] = world_to_screen(cell_center(enemy.pos_at(alpha)), grid_height);
+                enemy_sprite.x = x;
+                enemy_sprite.y = y;
+                enemy_sprite.w = (ENEMY_SIZE * TILE_SZ as f32) as u16;
+                enemy_sprite.h = (ENEMY_SIZE * TILE_SZ as f32) as u16;
+                enemy_sprite.rot = 0.0;
+            }
+
+            if let Some(enemy_sprite_gfx) = sprite_gfx.get_mut(sprite_index) {
+                *enemy_sprite_gfx = self.enemy_anims.region(enemy.anim, time - enemy.anim_start);
+            }
+        }
+
+        for (index, gold_pos) in game.golds.iter().enumerate() {
+            let sprite_index = player_sprite_index + 1 + game.enemies.len() + index;
+            if let Some(gold_sprite) = sprite_posns.get_mut(sprite_index) {
+                let [x, y] = world_to_screen(cell_center(*gold_pos), grid_height);
+                gold_sprite.x = x;
+                gold_sprite.y = y;
+                gold_sprite.w = TILE_SZ as u16 / 3;
+                gold_sprite.h = TILE_SZ as u16 / 3;
+                gold_sprite.rot = 0.0;
+            }
+
+            if let Some(gold_sprite_gfx) = sprite_gfx.get_mut(sprite_index) {
+                *gold_sprite_gfx = self.gold_anims.region(AnimState::Idle, time);
+            }
+        }
+
+        // Doors take the last slots
+        let first_door_index = total_sprites_needed - game.doors.len();
+        for (index, door) in game.doors.iter().enumerate() {
+            let sprite_index = first_door_index + index;
+            let [x, y] = world_to_screen(cell_center(door.pos), grid_height);
+            sprite_posns[sprite_index] = Transform {
+                x,
+                y,
+                w: TILE_SZ as u16,
+                h: TILE_SZ as u16,
+                rot: 0.0,
+            };
+            sprite_gfx[sprite_index] = DOOR;
+        }
+    }
+
+    fn render_hud(&mut self, game: &Game, frend: &mut Renderer) {
+        let text = &mut self.text;
+        text.clear(frend);
+        let top = H as f32 - 12.0;
+        text.draw_text(frend, [4.0, top], &format!("GOLD: {}", game.score), 1.0);
+        let time = format!("TIME: {:02}", game.time_left().ceil() as u32);
+        let right = W as f32 - 4.0 - text.font().width(&time, 1.0);
+        text.draw_text(frend, [right, top], &time, 1.0);
+        let mid = W as f32 / 2.0;
+        match game.state {
+            GameState::Title => {
+                text.draw_centered(frend, mid, 140.0, "COLLECT THE GOLD!", 2.0);
+                text.draw_centered(frend, mid, 100.0, "PRESS ENTER TO START", 1.0);
+                text.draw_centered(frend, mid, 86.0, "ARROWS MOVE, P PAUSES", 1.0);
+            }
+            GameState::Paused => text.draw_centered(frend, mid, 112.0, "PAUSED", 2.0),
+            GameState::Dead => text.draw_centered(frend, mid, 112.0, "YOU DIED", 2.0),
+            _ => {}
+        }
+    }
+
+    /// The screen for typing initials after a win.
+    fn render_initials(&mut self, entry: &InitialsEntry, score: u32, frend: &mut Renderer) {
+        frend.sprite_group_resize(0, 0);
+        let mid = W as f32 / 2.0;
+        let text = &mut self.text;
+        text.clear(frend);
+        let heading = format!("YOU WIN! GOLD: {score}");
+        text.draw_centered(frend, mid, 176.0, &heading, 1.0);
+        text.draw_centered(frend, mid, 146.0, "ENTER YOUR INITIALS", 1.0);
+        // letters spaced out so the cursor mark fits under one
+        let spaced: Vec<String> = entry.initials().chars().map(String::from).collect();
+        text.draw_centered(frend, mid, 112.0, &spaced.join(" "), 2.0);
+        let mut mark = vec![" "; spaced.len()];
+        mark[entry.cursor()] = "^";
+        text.draw_centered(frend, mid, 96.0, &mark.join(" "), 2.0);
+        text.draw_centered(frend, mid, 56.0, "TYPE OR USE ARROWS", 1.0);
+        text.draw_centered(frend, mid, 44.0, "ENTER TO SAVE", 1.0);
+    }
+
+    /// The best runs so far, overall and from `level`.
+    fn render_leaderboard(&mut self, board: &Leaderboard, level: &str, frend: &mut Renderer) {
+        frend.sprite_group_resize(0, 0);
+        let mid = W as f32 / 2.0;
+        let line = |rank: usize, entry: &Entry| {
+            format!("{:>2}. {:<3} {:>5}", rank + 1, entry.initials, entry.score)
+        };
+        let text = &mut self.text;
+        text.clear(frend);
+        text.draw_centered(frend, mid, 211.0, "HIGH SCORES", 1.0);
+        for (rank, entry) in board.top(7).enumerate() {
+            let y = 193.0 - rank as f32 * 12.0;
+            text.draw_centered(frend, mid, y, &line(rank, entry), 1.0);
+        }
+        let heading = format!("BEST FROM {}", level.to_uppercase());
+        text.draw_centered(frend, mid, 101.0, &heading, 1.0);
+        for (rank, entry) in board.top_for_level(level, 3).enumerate() {
+            let y = 83.0 - rank as f32 * 12.0;
+            text.draw_centered(frend, mid, y, &line(rank, entry), 1.0);
+        }
+        text.draw_centered(frend, mid, 26.0, "PRESS ENTER TO CONTINUE", 1.0);
+    }
+}
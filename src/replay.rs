@@ -0,0 +1,275 @@
+use crate::game::{DEATH_DELAY, ROUND_LENGTH};
+use crate::DT;
+use frenderer::input::{Input, Key};
+use std::str::FromStr;
+
+// The longest a run can go is a full round plus dying at the very end.
+// Replays get twice that before they're treated as corrupt.
+const MAX_STEPS: usize = ((ROUND_LENGTH + DEATH_DELAY) / DT) as usize * 2;
+
+/// Which of the keys the game reads were held during one simulation step.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct Controls(u8);
+
+impl Controls {
+    pub const LEFT: u8 = 1 << 0;
+    pub const RIGHT: u8 = 1 << 1;
+    pub const UP: u8 = 1 << 2;
+    pub const DOWN: u8 = 1 << 3;
+    const ALL: u8 = Self::LEFT | Self::RIGHT | Self::UP | Self::DOWN;
+
+    /// Controls with the keys in `bits` (made of `Controls::LEFT` etc.) held.
+    pub fn from_bits(bits: u8) -> Self {
+        Self(bits & Self::ALL)
+    }
+
+    pub fn from_input(input: &Input) -> Self {
+        let mut bits = 0;
+        for (key, bit) in [
+            (Key::ArrowLeft, Self::LEFT),
+            (Key::ArrowRight, Self::RIGHT),
+            (Key::ArrowUp, Self::UP),
+            (Key::ArrowDown, Self::DOWN),
+        ] {
+            if input.is_key_down(key) {
+                bits |= bit;
+            }
+        }
+        Self::from_bits(bits)
+    }
+    fn held(self, bit: u8) -> f32 {
+        if self.0 & bit != 0 {
+            1.0
+        } else {
+            0.0
+        }
+    }
+    /// -1 for left, 1 for right, like `Input::key_axis`.
+    pub fn x_axis(self) -> f32 {
+        self.held(Self::RIGHT) - self.held(Self::LEFT)
+    }
+    /// -1 for up, 1 for down, like `Input::key_axis`.
+    pub fn y_axis(self) -> f32 {
+        self.held(Self::DOWN) - self.held(Self::UP)
+    }
+}
+
+/// How a run ended, recorded so a replay can check it got the same result.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Outcome {
+    pub score: u32,
+    pub alive: bool,
+    pub level: String,
+    pub player: (f32, f32),
+}
+
+impl std::fmt::Display for Outcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "score={} alive={} level={} x={} y={}",
+            self.score, self.alive, self.level, self.player.0, self.player.1
+        )
+    }
+}
+
+impl FromStr for Outcome {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, ()> {
+        let mut fields = s.split_whitespace().map(|field| field.split_once('='));
+        let mut next = |name: &str| match fields.next() {
+            Some(Some((key, val))) if key == name => Ok(val),
+            _ => Err(()),
+        };
+        Ok(Outcome {
+            score: next("score")?.parse().map_err(|_| ())?,
+            alive: next("alive")?.parse().map_err(|_| ())?,
+            level: next("level")?.to_string(),
+            player: (
+                next("x")?.parse().map_err(|_| ())?,
+                next("y")?.parse().map_err(|_| ())?,
+            ),
+        })
+    }
+}
+
+/// The seed and per-step controls of one run.  Feeding the same controls
+/// to a game built with the same seed plays the run out identically.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Replay {
+    pub seed: u64,
+    steps: Vec<Controls>,
+    pub outcome: Option<Outcome>,
+}
+
+impl Replay {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            steps: Vec::new(),
+            outcome: None,
+        }
+    }
+    pub fn record(&mut self, controls: Controls) {
+        self.steps.push(controls);
+    }
+    pub fn steps(&self) -> &[Controls] {
+        &self.steps
+    }
+}
+
+/// Where a replay file stopped making sense.  `line` is 1-based.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ReplayParseError {
+    pub line: usize,
+    pub message: &'static str,
+}
+
+impl std::fmt::Display for ReplayParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for ReplayParseError {}
+
+/*
+Replays are small text files.  Held keys rarely change from one step to
+the next, so steps are stored as runs of COUNT steps with the same KEYS
+bits:
+
+replay 1
+seed SEED
+outcome score=N alive=BOOL level=NAME x=X y=Y
+COUNT KEYS
+COUNT KEYS
+...
+
+The outcome line is left out if the run never finished.  No replay holds
+more steps than twice the longest possible run.
+*/
+impl std::fmt::Display for Replay {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "replay 1")?;
+        writeln!(f, "seed {}", self.seed)?;
+        if let Some(outcome) = &self.outcome {
+            writeln!(f, "outcome {outcome}")?;
+        }
+        let mut steps = self.steps.iter().peekable();
+        while let Some(&controls) = steps.next() {
+            let mut count = 1;
+            while steps.next_if_eq(&&controls).is_some() {
+                count += 1;
+            }
+            writeln!(f, "{count} {}", controls.0)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Replay {
+    type Err = ReplayParseError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut lines = s.lines().enumerate().map(|(idx, line)| (idx + 1, line));
+        let err = |line, message| ReplayParseError { line, message };
+        match lines.next() {
+            Some((_, "replay 1")) => {}
+            _ => return Err(err(1, "not a version 1 replay")),
+        }
+        let seed = match lines.next() {
+            Some((line, text)) => text
+                .strip_prefix("seed ")
+                .and_then(|seed| seed.parse().ok())
+                .ok_or(err(line, "expected `seed N`"))?,
+            None => return Err(err(2, "expected `seed N`")),
+        };
+        let mut replay = Replay::new(seed);
+        for (line, text) in lines {
+            if let Some(outcome) = text.strip_prefix("outcome ") {
+                if replay.outcome.is_some() || !replay.steps.is_empty() {
+                    return Err(err(line, "the outcome goes right after the seed"));
+                }
+                let outcome = outcome.parse().map_err(|_| {
+                    err(
+                        line,
+                        "expected `outcome score=N alive=BOOL level=NAME x=X y=Y`",
+                    )
+                })?;
+                replay.outcome = Some(outcome);
+                continue;
+            }
+            let run = text
+                .split_once(' ')
+                .and_then(|(count, keys)| Some((count.parse().ok()?, keys.parse().ok()?)));
+            let Some((count, keys)) = run else {
+                return Err(err(line, "expected `COUNT KEYS`"));
+            };
+            if keys & !Controls::ALL != 0 {
+                return Err(err(line, "unknown keys in run"));
+            }
+            if count > MAX_STEPS - replay.steps.len() {
+                return Err(err(line, "more steps than a run can take"));
+            }
+            replay
+                .steps
+                .extend(std::iter::repeat_n(Controls(keys), count));
+        }
+        Ok(replay)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_axes() {
+        assert_eq!(Controls::default().x_axis(), 0.0);
+        assert_eq!(Controls(Controls::LEFT).x_axis(), -1.0);
+        assert_eq!(Controls(Controls::RIGHT | Controls::DOWN).x_axis(), 1.0);
+        assert_eq!(Controls(Controls::RIGHT | Controls::DOWN).y_axis(), 1.0);
+        assert_eq!(Controls(Controls::UP | Controls::DOWN).y_axis(), 0.0);
+    }
+    #[test]
+    fn test_round_trip() {
+        let mut replay = Replay::new(1234);
+        for controls in [0, 0, 0, 1, 1, 9, 0, 0] {
+            replay.record(Controls(controls));
+        }
+        let text = replay.to_string();
+        assert_eq!(text, "replay 1\nseed 1234\n3 0\n2 1\n1 9\n2 0\n");
+        assert_eq!(text.parse::<Replay>().unwrap(), replay);
+        replay.outcome = Some(Outcome {
+            score: 3,
+            alive: false,
+            level: "level2".to_string(),
+            player: (1.25, 0.1),
+        });
+        assert_eq!(replay.to_string().parse::<Replay>().unwrap(), replay);
+    }
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!("".parse::<Replay>().unwrap_err().line, 1);
+        assert_eq!("replay 1\nseed x\n".parse::<Replay>().unwrap_err().line, 2);
+        assert_eq!(
+            "replay 1\nseed 1\n3 0\n3\n"
+                .parse::<Replay>()
+                .unwrap_err()
+                .line,
+            4
+        );
+        assert_eq!(
+            "replay 1\nseed 1\n3 64\n"
+                .parse::<Replay>()
+                .unwrap_err()
+                .line,
+            3
+        );
+        // runs too long for any game, alone or added up
+        let long = "replay 1\nseed 1\n99999999999 0\n".parse::<Replay>();
+        assert_eq!(long.unwrap_err().line, 3);
+        let runs = format!("replay 1\nseed 1\n{MAX_STEPS} 0\n1 1\n");
+        assert_eq!(runs.parse::<Replay>().unwrap_err().line, 4);
+        let full = format!("replay 1\nseed 1\n{} 0\n1 1\n", MAX_STEPS - 1);
+        assert_eq!(full.parse::<Replay>().unwrap().steps().len(), MAX_STEPS);
+    }
+}
@@ -0,0 +1,136 @@
+use assets_manager::{asset::Png, AssetCache};
+use frenderer::{
+    sprites::{Camera2D, SheetRegion, Transform},
+    wgpu, Renderer,
+};
+
+/// Where a bitmap font's glyphs sit in its sheet: printable ASCII from
+/// `first` on, in rows of `columns` square glyphs `pitch` pixels apart.
+pub struct Font {
+    pub sheet: &'static str,
+    pub origin: [u16; 2],
+    pub glyph_size: u16,
+    pub pitch: u16,
+    pub columns: u16,
+    pub first: char,
+    pub last: char,
+}
+
+/// The font at the bottom left of the tilesheet.
+pub const TILESHEET_FONT: Font = Font {
+    sheet: "tilesheet",
+    origin: [0, 738],
+    glyph_size: 8,
+    pitch: 9,
+    columns: 32,
+    first: ' ',
+    last: '~',
+};
+
+impl Font {
+    /// The sheet region for `c`; characters the font lacks are drawn as `?`.
+    pub fn glyph(&self, c: char) -> SheetRegion {
+        let c = if (self.first..=self.last).contains(&c) {
+            c
+        } else {
+            '?'
+        };
+        let idx = c as u16 - self.first as u16;
+        SheetRegion::new(
+            0,
+            self.origin[0] + (idx % self.columns) * self.pitch,
+            self.origin[1] + (idx / self.columns) * self.pitch,
+            0,
+            self.glyph_size as i16,
+            self.glyph_size as i16,
+        )
+    }
+
+    /// How wide `text` is when drawn at `scale`.
+    pub fn width(&self, text: &str, scale: f32) -> f32 {
+        text.chars().count() as f32 * self.glyph_size as f32 * scale
+    }
+}
+
+/// Text drawn in its own sprite group, with a camera that stays put so
+/// it works as a HUD.  Call [`Text::clear`] at the start of every frame,
+/// then [`Text::draw_text`] for each line.
+pub struct Text {
+    font: Font,
+    group: usize,
+    used: usize,
+}
+
+impl Text {
+    pub fn new(
+        frend: &mut Renderer,
+        cache: &AssetCache,
+        font: Font,
+        screen_size: [f32; 2],
+    ) -> Self {
+        let sheet_handle = cache
+            .load::<Png>(font.sheet)
+            .expect("Couldn't load font sheet img");
+        let sheet_img = sheet_handle.read().0.to_rgba8();
+        let sheet_tex = frend.create_array_texture(
+            &[&sheet_img],
+            wgpu::TextureFormat::Rgba8UnormSrgb,
+            sheet_img.dimensions(),
+            Some("font"),
+        );
+        let camera = Camera2D {
+            screen_pos: [0.0, 0.0],
+            screen_size,
+        };
+        // frenderer can't make an empty group, so start with room for a
+        // few lines and hide them
+        let group = frend.sprite_group_add(
+            &sheet_tex,
+            vec![Transform::ZERO; 64],
+            vec![SheetRegion::ZERO; 64],
+            camera,
+        );
+        frend.sprite_group_resize(group, 0);
+        Self {
+            font,
+            group,
+            used: 0,
+        }
+    }
+
+    pub fn font(&self) -> &Font {
+        &self.font
+    }
+
+    /// Removes everything drawn so far.
+    pub fn clear(&mut self, frend: &mut Renderer) {
+        self.used = 0;
+        frend.sprite_group_resize(self.group, 0);
+    }
+
+    /// Draws `text` on one line with the bottom left corner of its first
+    /// glyph at `pos`, each glyph `scale` times its size in the sheet.
+    pub fn draw_text(&mut self, frend: &mut Renderer, pos: [f32; 2], text: &str, scale: f32) {
+        let size = self.font.glyph_size as f32 * scale;
+        let start = self.used;
+        self.used += text.chars().count();
+        frend.sprite_group_resize(self.group, self.used);
+        let (trfs, uvs) = frend.sprites_mut(self.group, start..self.used);
+        for (idx, c) in text.chars().enumerate() {
+            trfs[idx] = Transform {
+                x: pos[0] + size * (idx as f32 + 0.5),
+                y: pos[1] + size / 2.0,
+                w: size as u16,
+                h: size as u16,
+                rot: 0.0,
+            };
+            uvs[idx] = self.font.glyph(c);
+        }
+    }
+
+    /// Draws `text` centered on `x`, with the bottom of the line at `y`.
+    pub fn draw_centered(&mut self, frend: &mut Renderer, x: f32, y: f32, text: &str, scale: f32) {
+        let left = x - self.font.width(text, scale) / 2.0;
+        self.draw_text(frend, [left, y], text, scale);
+    }
+}